@@ -0,0 +1,24 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+/// Generates the C header of the `ffi` module into `OUT_DIR`, as build scripts must not modify
+/// the source tree. The checked-in copy in `include/` (so that the header is available without
+/// building the crate) is compared against it by the tests of the `ffi` module.
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .generate()
+        .expect("failed to generate C header")
+        .write_to_file(format!("{}/ic_canister_sig_creation.h", out_dir));
+}
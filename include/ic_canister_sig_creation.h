@@ -0,0 +1,148 @@
+#ifndef IC_CANISTER_SIG_CREATION_H
+#define IC_CANISTER_SIG_CREATION_H
+
+/* Generated by cbindgen from src/ffi.rs with the `ffi` feature, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The status of a call.
+typedef enum IcStatus {
+  IC_STATUS_OK = 0,
+  // A required pointer was null.
+  IC_STATUS_NULL_POINTER = 1,
+  // The input could not be parsed or is invalid.
+  IC_STATUS_INVALID_INPUT = 2,
+} IcStatus;
+
+// A byte buffer owned by the caller, to be released with [ic_canister_sig_bytes_free].
+typedef struct IcBytes {
+  uint8_t *data;
+  size_t len;
+} IcBytes;
+
+// A borrowed byte buffer, e.g. an element of an array of inputs.
+typedef struct IcSlice {
+  const uint8_t *data;
+  size_t len;
+} IcSlice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Releases a buffer returned by this library. Releasing an empty buffer (null data) is a no-op.
+//
+// # Safety
+// `bytes` must have been returned by this library and not been released before.
+void ic_canister_sig_bytes_free(struct IcBytes bytes);
+
+// Returns the message of the last error on the calling thread, as UTF-8 (not NUL-terminated).
+//
+// # Safety
+// `out` must be valid for writes.
+enum IcStatus ic_canister_sig_last_error(struct IcBytes *out);
+
+// Computes the DER-encoding of the canister signature public key with the given
+// canister id (as raw principal bytes) and seed.
+//
+// # Safety
+// The inputs must be valid for reads of the given lengths, `out` must be valid for writes.
+enum IcStatus ic_canister_sig_pk_to_der(const uint8_t *canister_id,
+                                        size_t canister_id_len,
+                                        const uint8_t *seed,
+                                        size_t seed_len,
+                                        struct IcBytes *out);
+
+// Computes the raw encoding (without DER-envelope) of the canister signature public key with
+// the given canister id (as raw principal bytes) and seed.
+//
+// # Safety
+// The inputs must be valid for reads of the given lengths, `out` must be valid for writes.
+enum IcStatus ic_canister_sig_pk_to_raw(const uint8_t *canister_id,
+                                        size_t canister_id_len,
+                                        const uint8_t *seed,
+                                        size_t seed_len,
+                                        struct IcBytes *out);
+
+// Parses a DER-encoded canister signature public key into its canister id
+// (as raw principal bytes) and seed.
+//
+// # Safety
+// `pk_der` must be valid for reads of `pk_der_len` bytes, the outputs must be valid for writes.
+enum IcStatus ic_canister_sig_pk_from_der(const uint8_t *pk_der,
+                                          size_t pk_der_len,
+                                          struct IcBytes *canister_id_out,
+                                          struct IcBytes *seed_out);
+
+// Parses a raw canister signature public key (without DER-envelope) into its canister id
+// (as raw principal bytes) and seed.
+//
+// # Safety
+// `pk_raw` must be valid for reads of `pk_raw_len` bytes, the outputs must be valid for writes.
+enum IcStatus ic_canister_sig_pk_from_raw(const uint8_t *pk_raw,
+                                          size_t pk_raw_len,
+                                          struct IcBytes *canister_id_out,
+                                          struct IcBytes *seed_out);
+
+// Derives the self-authenticating principal of a DER-encoded canister signature public key,
+// as raw principal bytes.
+//
+// # Safety
+// `pk_der` must be valid for reads of `pk_der_len` bytes, `out` must be valid for writes.
+enum IcStatus ic_canister_sig_pk_to_principal(const uint8_t *pk_der,
+                                              size_t pk_der_len,
+                                              struct IcBytes *out);
+
+// Returns the textual representation of the given raw principal, as UTF-8 (not NUL-terminated).
+//
+// # Safety
+// `principal_bytes` must be valid for reads of `principal_len` bytes, `out` must be valid for writes.
+enum IcStatus ic_canister_sig_principal_to_text(const uint8_t *principal_bytes,
+                                                size_t principal_len,
+                                                struct IcBytes *out);
+
+// Computes the signing input of a delegation, see [delegation_signature_msg]. The targets are
+// raw principals; if `targets` is null, the delegation is unrestricted.
+//
+// # Safety
+// `pubkey` must be valid for reads of `pubkey_len` bytes, `targets` (if not null) must be valid
+// for reads of `targets_len` slices, each valid for reads of its length, and `out` must be
+// valid for writes.
+enum IcStatus ic_canister_sig_delegation_msg(const uint8_t *pubkey,
+                                             size_t pubkey_len,
+                                             uint64_t expiration,
+                                             const struct IcSlice *targets,
+                                             size_t targets_len,
+                                             struct IcBytes *out);
+
+// Hashes the given message with the given domain separator, see [hash_with_domain].
+//
+// # Safety
+// The inputs must be valid for reads of the given lengths, `out` must be valid for writes
+// of 32 bytes.
+enum IcStatus ic_canister_sig_hash_with_domain(const uint8_t *domain,
+                                               size_t domain_len,
+                                               const uint8_t *message,
+                                               size_t message_len,
+                                               uint8_t (*out)[32]);
+
+// Parses a CBOR-encoded canister signature, see [parse_canister_sig_cbor], and returns its
+// CBOR-encoded certificate and the root hash of its tree.
+//
+// # Safety
+// `signature` must be valid for reads of `signature_len` bytes, `certificate_out` must be
+// valid for writes and `tree_digest_out` must be valid for writes of 32 bytes.
+enum IcStatus ic_canister_sig_parse(const uint8_t *signature,
+                                    size_t signature_len,
+                                    struct IcBytes *certificate_out,
+                                    uint8_t (*tree_digest_out)[32]);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* IC_CANISTER_SIG_CREATION_H */
@@ -0,0 +1,364 @@
+//! Command-line tool to inspect and verify canister signatures and their public keys.
+//!
+//! All commands work offline. Binary arguments (keys, signatures and messages) are given either
+//! in hex, or as the path of a file containing them in hex or in binary.
+use candid::Principal;
+use clap::{Parser, Subcommand};
+use ic_canister_sig_creation::session_key::SessionPublicKey;
+use ic_canister_sig_creation::verification::{verify_canister_sig, SignatureVerifier};
+use ic_canister_sig_creation::{
+    delegation_signature_msg, extract_raw_root_pk_from_der, parse_canister_sig_cbor,
+    parse_certificate_cbor, CanisterSigPublicKey, SignatureDomain, IC_ROOT_PUBLIC_KEY,
+};
+use std::path::Path;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "ic-canister-sig", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decodes a canister signature public key (DER, hex-encoded DER or PEM).
+    DecodeKey {
+        /// The key, or the path of a file containing it.
+        key: String,
+    },
+    /// Pretty-prints a CBOR-encoded canister signature, including its certificate.
+    InspectSig {
+        /// The signature, or the path of a file containing it.
+        signature: String,
+    },
+    /// Computes the signing input of a delegation, cf. `delegation_signature_msg`.
+    DelegationMsg {
+        /// The DER-encoded public key that is delegated to.
+        #[arg(long)]
+        pubkey: String,
+        /// The expiration of the delegation, in nanoseconds since the UNIX epoch.
+        #[arg(long)]
+        expiration: u64,
+        /// A canister the delegation is restricted to (may be repeated).
+        #[arg(long = "target")]
+        targets: Vec<Principal>,
+    },
+    /// Verifies a canister signature on a message.
+    Verify {
+        /// The canister signature public key.
+        #[arg(long)]
+        public_key: String,
+        /// The CBOR-encoded canister signature.
+        #[arg(long)]
+        signature: String,
+        /// The signed message (without domain separator).
+        #[arg(long)]
+        message: String,
+        /// The signature domain.
+        #[arg(long, default_value = "ic-request-auth-delegation")]
+        domain: String,
+        /// A file containing the DER-encoded root public key (in hex or in binary),
+        /// the IC mainnet root key is used if not given.
+        #[arg(long)]
+        root_key: Option<String>,
+    },
+}
+
+/// Verifies BLS signatures of certificates. Other signatures are not needed to verify
+/// canister signatures.
+struct BlsVerifier;
+
+impl SignatureVerifier for BlsVerifier {
+    fn verify_bls_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        ic_verify_bls_signature::verify_bls_signature(signature, message, public_key)
+            .map_err(|_| "BLS signature verification failed".to_string())
+    }
+
+    fn verify_basic_signature(
+        &self,
+        public_key: &SessionPublicKey,
+        _message: &[u8],
+        _signature: &[u8],
+    ) -> Result<(), String> {
+        Err(format!(
+            "verification of {} signatures is not supported",
+            public_key.algorithm()
+        ))
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs the given command, returning its output.
+fn run(command: Command) -> Result<String, String> {
+    match command {
+        Command::DecodeKey { key } => {
+            let public_key = parse_public_key(&key)?;
+            Ok(format!(
+                "canister id: {}\nseed:        {}\nprincipal:   {}\nDER:         {}\n",
+                public_key.canister_id,
+                hex::encode(&public_key.seed),
+                public_key.to_principal(),
+                hex::encode(public_key.to_der())
+            ))
+        }
+        Command::InspectSig { signature } => {
+            let signature = parse_canister_sig_cbor(&read_bytes(&signature)?)?;
+            let certificate = parse_certificate_cbor(signature.certificate())?;
+            let mut output = format!(
+                "certificate.tree: {:#?}\ncertificate.signature: {}\n",
+                certificate.tree,
+                hex::encode(&certificate.signature)
+            );
+            if let Some(delegation) = &certificate.delegation {
+                let subnet_id = Principal::try_from_slice(&delegation.subnet_id)
+                    .map_err(|e| format!("invalid subnet id: {}", e))?;
+                let subnet_certificate = parse_certificate_cbor(&delegation.certificate)?;
+                output += &format!(
+                    "certificate.delegation.subnet_id: {}\n\
+                     certificate.delegation.certificate.tree: {:#?}\n",
+                    subnet_id, subnet_certificate.tree
+                );
+            }
+            output += &format!(
+                "tree: {:#?}\ntree digest: {}\n",
+                signature.tree(),
+                hex::encode(signature.tree().digest())
+            );
+            Ok(output)
+        }
+        Command::DelegationMsg {
+            pubkey,
+            expiration,
+            targets,
+        } => {
+            let targets = (!targets.is_empty()).then_some(targets);
+            let msg =
+                delegation_signature_msg(&read_bytes(&pubkey)?, expiration, targets.as_deref());
+            Ok(format!("{}\n", hex::encode(msg)))
+        }
+        Command::Verify {
+            public_key,
+            signature,
+            message,
+            domain,
+            root_key,
+        } => {
+            let root_public_key = match root_key {
+                Some(path) => extract_raw_root_pk_from_der(&read_file(Path::new(&path))?)?,
+                None => IC_ROOT_PUBLIC_KEY.clone(),
+            };
+            verify_canister_sig(
+                &BlsVerifier,
+                &root_public_key,
+                &parse_public_key(&public_key)?,
+                SignatureDomain::new(domain.as_bytes())?,
+                &read_bytes(&message)?,
+                &read_bytes(&signature)?,
+            )?;
+            Ok("valid signature\n".to_string())
+        }
+    }
+}
+
+fn parse_public_key(arg: &str) -> Result<CanisterSigPublicKey, String> {
+    let pem = match Path::new(arg).is_file() {
+        true => std::fs::read_to_string(arg).ok(),
+        false => Some(arg.to_string()),
+    };
+    match pem {
+        Some(pem) if pem.trim_start().starts_with("-----BEGIN") => {
+            CanisterSigPublicKey::from_pem(&pem)
+        }
+        _ => CanisterSigPublicKey::try_from(read_bytes(arg)?.as_slice()),
+    }
+}
+
+/// Reads the given argument as hex, or as the path of a file.
+fn read_bytes(arg: &str) -> Result<Vec<u8>, String> {
+    let path = Path::new(arg);
+    if path.is_file() {
+        return read_file(path);
+    }
+    hex::decode(arg.trim()).map_err(|e| format!("'{}' is neither a file nor hex: {}", arg, e))
+}
+
+/// Reads the given file, decoding its contents from hex if possible.
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    match std::str::from_utf8(&bytes).map(|text| hex::decode(text.trim())) {
+        Ok(Ok(decoded)) => Ok(decoded),
+        _ => Ok(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_canister_sig_creation::{certificate_to_cbor, CanisterSig, IC_ROOT_PK_DER_PREFIX};
+    use ic_certification::{fork, labeled, leaf, pruned, Certificate};
+    use ic_verify_bls_signature::PrivateKey;
+
+    const CANISTER_SIG_PK_DER_HEX: &str =
+        "301f300c060a2b0601040183b8430102030f000a000000000000000001012a482c";
+    // The first signature vector of `test_vectors.json`.
+    const SIGNATURE_VECTOR_PK_DER_HEX: &str =
+        "3022300c060a2b0601040183b84301020312000a00000000000000000101736565642d31";
+    const SIGNATURE_VECTOR_MESSAGE_HEX: &str =
+        "c96fadf2f339f22bae18416baf67e444828dea90da1bd3ed2af0ac70366ead90";
+    const SIGNATURE_VECTOR_CBOR_HEX: &str = "d9d9f7a26b63657274696669636174655892d9d9f7a2647472656583024863616e697374657283024a0000000000000000010183024e6365727469666965645f646174618203582019b170e5995bbe85c545fea511b74d554a2fdbb16e1db7e0201c07321bf7041a697369676e6174757265583000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000064747265658302437369678301830258200eb026731d9ea3f870511f8c18daeb814eaa2c9e276082b204f2a962212fb5bd8301820458203cdce89e7ca429090155b468859b1b80a0958759224714b59526888e91ead95583025820bb8d3268a5531880bbff56c74a9ee6836ce8aad0866bab4aae9fff72b9758f8582034082045820606ee0225a328cc385acb19f43f95e4f83ce12185116884bbcc08d71e819166e";
+
+    #[test]
+    fn should_read_hex_arguments() {
+        assert_eq!(read_bytes("0a0b"), Ok(vec![0x0a, 0x0b]));
+        assert!(read_bytes("not hex").is_err());
+    }
+
+    #[test]
+    fn should_read_hex_and_binary_files() {
+        let dir = std::env::temp_dir();
+        let hex_file = dir.join(format!("ic-canister-sig-test-{}.hex", std::process::id()));
+        let bin_file = dir.join(format!("ic-canister-sig-test-{}.bin", std::process::id()));
+        std::fs::write(&hex_file, "0a0b\n").unwrap();
+        std::fs::write(&bin_file, [0xff, 0x00]).unwrap();
+
+        assert_eq!(read_bytes(hex_file.to_str().unwrap()), Ok(vec![0x0a, 0x0b]));
+        assert_eq!(read_bytes(bin_file.to_str().unwrap()), Ok(vec![0xff, 0x00]));
+
+        std::fs::remove_file(hex_file).unwrap();
+        std::fs::remove_file(bin_file).unwrap();
+    }
+
+    #[test]
+    fn should_parse_public_key_from_hex_and_pem() {
+        let public_key = parse_public_key(CANISTER_SIG_PK_DER_HEX).unwrap();
+        assert_eq!(hex::encode(public_key.to_der()), CANISTER_SIG_PK_DER_HEX);
+        assert_eq!(parse_public_key(&public_key.to_pem()), Ok(public_key));
+    }
+
+    #[test]
+    fn should_decode_key() {
+        let command = Command::DecodeKey {
+            key: CANISTER_SIG_PK_DER_HEX.to_string(),
+        };
+        let output = run(command).unwrap();
+        assert!(output.contains("canister id: rwlgt-iiaaa-aaaaa-aaaaa-cai\n"));
+        assert!(output.contains("seed:        2a482c\n"));
+        assert!(output.contains(&format!("DER:         {}\n", CANISTER_SIG_PK_DER_HEX)));
+    }
+
+    #[test]
+    fn should_compute_delegation_msg() {
+        let target = Principal::from_text("rdmx6-jaaaa-aaaaa-aaadq-cai").unwrap();
+        let command = Command::DelegationMsg {
+            pubkey: CANISTER_SIG_PK_DER_HEX.to_string(),
+            expiration: 42,
+            targets: vec![target],
+        };
+        let pubkey = hex::decode(CANISTER_SIG_PK_DER_HEX).unwrap();
+        let expected = delegation_signature_msg(&pubkey, 42, Some(&[target]));
+        assert_eq!(run(command), Ok(format!("{}\n", hex::encode(expected))));
+    }
+
+    #[test]
+    fn should_inspect_signature_with_pruned_nodes() {
+        let certificate = Certificate {
+            tree: fork(
+                pruned([1; 32]),
+                labeled(b"time", leaf(b"\x87\xad\x4b".to_vec())),
+            ),
+            signature: vec![2; 48],
+            delegation: None,
+        };
+        let certificate_cbor = certificate_to_cbor(&certificate).unwrap();
+        let tree = fork(pruned([3; 32]), labeled(b"sig", leaf(b"".to_vec())));
+        let signature_cbor = CanisterSig::new(certificate_cbor, tree.clone())
+            .to_cbor()
+            .unwrap();
+
+        let command = Command::InspectSig {
+            signature: hex::encode(signature_cbor),
+        };
+        let output = run(command).unwrap();
+        assert!(output.starts_with(&format!("certificate.tree: {:#?}\n", certificate.tree)));
+        assert!(output.contains(&format!("certificate.signature: {}\n", "02".repeat(48))));
+        assert!(output.contains(&format!("tree: {:#?}\n", tree)));
+        assert!(output.contains(&hex::encode([3; 32])));
+        assert!(output.ends_with(&format!("tree digest: {}\n", hex::encode(tree.digest()))));
+        assert!(!output.contains("certificate.delegation"));
+    }
+
+    #[test]
+    fn should_verify_valid_signature() {
+        // The signature vector of `test_vectors.json`, whose certificate is re-signed with
+        // a fixed root key, as the signature of the vector depends on the keys of the IC.
+        let signature =
+            parse_canister_sig_cbor(&hex::decode(SIGNATURE_VECTOR_CBOR_HEX).unwrap()).unwrap();
+        let mut certificate = parse_certificate_cbor(signature.certificate()).unwrap();
+        let root_key = PrivateKey::deserialize(&[42; 32]).unwrap();
+        let message = [
+            &[SignatureDomain::IC_STATE_ROOT.as_bytes().len() as u8][..],
+            SignatureDomain::IC_STATE_ROOT.as_bytes(),
+            &certificate.tree.digest(),
+        ]
+        .concat();
+        certificate.signature = root_key.sign(&message).serialize().to_vec();
+        let signature = CanisterSig::new(
+            certificate_to_cbor(&certificate).unwrap(),
+            signature.tree().clone(),
+        );
+        let root_key_file =
+            std::env::temp_dir().join(format!("ic-canister-sig-test-{}.der", std::process::id()));
+        let root_key_der = [
+            &IC_ROOT_PK_DER_PREFIX[..],
+            &root_key.public_key().serialize(),
+        ]
+        .concat();
+        std::fs::write(&root_key_file, root_key_der).unwrap();
+
+        let command = |message: &str| Command::Verify {
+            public_key: SIGNATURE_VECTOR_PK_DER_HEX.to_string(),
+            signature: hex::encode(signature.to_cbor().unwrap()),
+            message: message.to_string(),
+            domain: "ic-request-auth-delegation".to_string(),
+            root_key: Some(root_key_file.to_str().unwrap().to_string()),
+        };
+        assert_eq!(
+            run(command(SIGNATURE_VECTOR_MESSAGE_HEX)),
+            Ok("valid signature\n".to_string())
+        );
+        assert!(run(command("00")).is_err());
+
+        std::fs::remove_file(root_key_file).unwrap();
+    }
+
+    #[test]
+    fn should_reject_invalid_signature() {
+        let command = Command::Verify {
+            public_key: CANISTER_SIG_PK_DER_HEX.to_string(),
+            signature: "d9d9f7a0".to_string(),
+            message: "00".to_string(),
+            domain: "ic-request".to_string(),
+            root_key: None,
+        };
+        assert!(run(command).is_err());
+    }
+}
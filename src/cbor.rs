@@ -0,0 +1,253 @@
+//! CBOR encoding and decoding used throughout the crate.
+//!
+//! All CBOR handling goes through this module, so that the backend (currently `ciborium`)
+//! can be exchanged without affecting the on-wire format.
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde::Serialize;
+
+pub(crate) use ciborium::Value;
+
+/// The self-describing CBOR tag (cf. https://www.rfc-editor.org/rfc/rfc8949#section-3.4.6),
+/// required by the interface spec to prefix canister signatures.
+pub(crate) const SELF_DESCRIBING_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// Encodes the given value as CBOR.
+pub(crate) fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, String> {
+    let mut cbor = Vec::new();
+    ciborium::into_writer(value, &mut cbor).map_err(|e| e.to_string())?;
+    Ok(cbor)
+}
+
+/// Encodes the given value as CBOR, prefixed with the self-describing CBOR tag.
+pub(crate) fn to_vec_self_describing<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, String> {
+    let mut cbor = SELF_DESCRIBING_TAG.to_vec();
+    ciborium::into_writer(value, &mut cbor).map_err(|e| e.to_string())?;
+    Ok(cbor)
+}
+
+/// Converts the given value to its CBOR data model representation, without encoding it.
+pub(crate) fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, String> {
+    Value::serialized(value).map_err(|e| e.to_string())
+}
+
+/// Decodes the given bytes as a single CBOR item, failing if there is trailing data.
+/// Tags (e.g. the self-describing tag) in front of the item are ignored.
+///
+/// The item is decoded in two steps, via a [Value]: `ciborium` cannot lend borrowed byte
+/// strings from its input, which some types require (e.g. the pruned nodes of a `HashTree`).
+pub(crate) fn from_slice<T: DeserializeOwned>(cbor: &[u8]) -> Result<T, String> {
+    let mut reader = cbor;
+    let value: Value = ciborium::from_reader(&mut reader).map_err(|e| e.to_string())?;
+    if !reader.is_empty() {
+        return Err(format!("{} trailing bytes after CBOR item", reader.len()));
+    }
+    T::deserialize(ValueDeserializer(&value)).map_err(|e| e.to_string())
+}
+
+/// Deserializes from a borrowed [Value], lending its byte and text strings.
+struct ValueDeserializer<'de>(&'de Value);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Integer(integer) => {
+                let integer = i128::from(*integer);
+                if let Ok(unsigned) = u64::try_from(integer) {
+                    visitor.visit_u64(unsigned)
+                } else if let Ok(signed) = i64::try_from(integer) {
+                    visitor.visit_i64(signed)
+                } else {
+                    visitor.visit_i128(integer)
+                }
+            }
+            Value::Bytes(bytes) => visitor.visit_borrowed_bytes(bytes),
+            Value::Float(float) => visitor.visit_f64(*float),
+            Value::Text(text) => visitor.visit_borrowed_str(text),
+            Value::Bool(bool) => visitor.visit_bool(*bool),
+            Value::Null => visitor.visit_unit(),
+            Value::Tag(_, value) => ValueDeserializer(value).deserialize_any(visitor),
+            Value::Array(values) => {
+                visitor.visit_seq(SeqDeserializer::new(values.iter().map(ValueDeserializer)))
+            }
+            Value::Map(entries) => visitor.visit_map(MapDeserializer::new(
+                entries
+                    .iter()
+                    .map(|(key, value)| (ValueDeserializer(key), ValueDeserializer(value))),
+            )),
+            _ => Err(de::Error::custom("unsupported CBOR item")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            Value::Tag(_, value) => ValueDeserializer(value).deserialize_option(visitor),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Text(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            Value::Map(entries) if entries.len() == 1 => visitor.visit_enum(EnumDeserializer {
+                variant: &entries[0].0,
+                value: &entries[0].1,
+            }),
+            Value::Tag(_, value) => {
+                ValueDeserializer(value).deserialize_enum(name, variants, visitor)
+            }
+            _ => Err(de::Error::custom("expected an enum variant")),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, de::value::Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// An enum variant with data, encoded as a single-entry map from the variant name to the data.
+struct EnumDeserializer<'de> {
+    variant: &'de Value,
+    value: &'de Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = de::value::Error;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(ValueDeserializer(self.variant))?;
+        Ok((variant, ValueDeserializer(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = de::value::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use serde_bytes::ByteBuf;
+
+    #[test]
+    fn should_prefix_with_self_describing_tag() {
+        let cbor = to_vec_self_describing(&ByteBuf::from(vec![1, 2, 3])).unwrap();
+        assert_eq!(hex::encode(cbor), "d9d9f743010203");
+    }
+
+    #[test]
+    fn should_ignore_self_describing_tag_when_decoding() {
+        let value: ByteBuf = from_slice(&hex::decode("d9d9f743010203").unwrap()).unwrap();
+        assert_eq!(value.as_slice(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn should_use_shortest_form_encoding() {
+        let cbor = to_vec(&(23u64, 24u64, 256u64, -1i64, "a", ByteBuf::new())).unwrap();
+        assert_eq!(hex::encode(cbor), "8617181819010020616140");
+    }
+
+    #[test]
+    fn should_fail_decoding_with_trailing_bytes() {
+        let result = from_slice::<ByteBuf>(&hex::decode("4301020300").unwrap());
+        assert_matches!(result, Err(e) if e.contains("trailing bytes"));
+    }
+
+    #[test]
+    fn should_decode_hash_tree_with_pruned_nodes() {
+        let tree = ic_certification::fork(
+            ic_certification::pruned([42; 32]),
+            ic_certification::labeled(b"sig", ic_certification::leaf(b"value")),
+        );
+        let decoded: ic_certification::HashTree = from_slice(&to_vec(&tree).unwrap()).unwrap();
+        assert_eq!(decoded, tree);
+    }
+
+    #[test]
+    fn should_decode_enums() {
+        #[derive(Eq, PartialEq, Debug, Serialize, serde::Deserialize)]
+        enum Enum {
+            Unit,
+            Newtype(u64),
+            Struct { bytes: ByteBuf, text: String },
+        }
+        let values = vec![
+            Enum::Unit,
+            Enum::Newtype(42),
+            Enum::Struct {
+                bytes: ByteBuf::from(vec![1, 2]),
+                text: "text".to_string(),
+            },
+        ];
+        let decoded: Vec<Enum> = from_slice(&to_vec(&values).unwrap()).unwrap();
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn should_fail_decoding_incomplete_cbor() {
+        let result = from_slice::<ByteBuf>(&hex::decode("430102").unwrap());
+        assert_matches!(result, Err(_));
+    }
+}
@@ -0,0 +1,278 @@
+//! Delegations as used in the authentication of IC requests, see
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec#authentication
+use crate::serde_helpers::{blob, nat64};
+use crate::signature_map::{AddedSignature, CanisterSigError, CanisterSigInputs, SignatureMap};
+use crate::{delegation_signature_msg, SignatureDomain};
+use candid::{CandidType, Principal};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::collections::HashSet;
+
+/// The maximum number of targets of a delegation accepted by the IC.
+pub const MAX_DELEGATION_TARGETS: usize = 1_000;
+
+const MINUTE_NS: u64 = 60 * 1_000_000_000;
+const HOUR_NS: u64 = 60 * MINUTE_NS;
+
+/// A delegation to `pubkey`, optionally restricted to the canisters in `targets`.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct Delegation {
+    #[serde(with = "blob")]
+    pub pubkey: ByteBuf,
+    /// The expiration of the delegation, in nanoseconds since the UNIX epoch.
+    #[serde(with = "nat64")]
+    pub expiration: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<Vec<Principal>>,
+}
+
+impl Delegation {
+    /// Returns the signing input of this delegation, cf. [delegation_signature_msg].
+    pub fn signature_msg(&self) -> Vec<u8> {
+        delegation_signature_msg(&self.pubkey, self.expiration, self.targets.as_deref())
+    }
+}
+
+/// Adds the signature on the given delegation for the given seed to the map. The signature
+/// expires with the delegation at the latest, see [SignatureMap::add_signature_until].
+pub fn add_delegation_signature(
+    signatures: &mut SignatureMap,
+    seed: &[u8],
+    delegation: &Delegation,
+) -> Result<AddedSignature, CanisterSigError> {
+    let message = delegation.signature_msg();
+    let sig_inputs = CanisterSigInputs {
+        domain: SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+        seed,
+        message: &message,
+    };
+    signatures.add_signature_until(&sig_inputs, delegation.expiration)
+}
+
+/// The policy for the expiration of issued delegations. All durations are in nanoseconds.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct DelegationPolicy {
+    /// The time to live of a delegation if none is requested.
+    pub default_ttl_ns: u64,
+    /// The maximum time to live of a delegation, longer requested ones are shortened.
+    pub max_ttl_ns: u64,
+    /// The minimum time a delegation must remain valid to be of use, shorter requested
+    /// time to lives are rejected.
+    pub min_remaining_validity_ns: u64,
+    /// The tolerated difference between the clock of the issuer and the clocks of
+    /// the parties checking the expiration of a delegation.
+    pub clock_skew_ns: u64,
+}
+
+impl Default for DelegationPolicy {
+    /// A default time to live of 30 minutes, at most 8 hours, a minimum remaining validity
+    /// of 1 minute and a clock skew of 30 seconds.
+    fn default() -> Self {
+        DelegationPolicy {
+            default_ttl_ns: 30 * MINUTE_NS,
+            max_ttl_ns: 8 * HOUR_NS,
+            min_remaining_validity_ns: MINUTE_NS,
+            clock_skew_ns: MINUTE_NS / 2,
+        }
+    }
+}
+
+impl DelegationPolicy {
+    /// Returns the expiration (in nanoseconds since the UNIX epoch) of a delegation issued now
+    /// with the given requested time to live, or the default time to live if `None`.
+    pub fn expiration(&self, requested_ttl_ns: Option<u64>) -> Result<u64, String> {
+        self.expiration_internal(requested_ttl_ns, time())
+    }
+
+    pub(crate) fn expiration_internal(
+        &self,
+        requested_ttl_ns: Option<u64>,
+        now: u64,
+    ) -> Result<u64, String> {
+        let ttl = requested_ttl_ns
+            .unwrap_or(self.default_ttl_ns)
+            .min(self.max_ttl_ns);
+        if ttl
+            < self
+                .min_remaining_validity_ns
+                .saturating_add(self.clock_skew_ns)
+        {
+            return Err(format!(
+                "delegation time to live too short: {} ns, at least {} ns required",
+                ttl,
+                self.min_remaining_validity_ns
+                    .saturating_add(self.clock_skew_ns)
+            ));
+        }
+        Ok(now.saturating_add(ttl))
+    }
+
+    /// Returns whether a delegation with the given expiration is still valid for at least
+    /// the minimum remaining validity, taking the clock skew into account. This can be used
+    /// e.g. to decide whether an existing delegation can be re-used or must be renewed.
+    pub fn has_min_remaining_validity(&self, expiration: u64) -> bool {
+        self.has_min_remaining_validity_internal(expiration, time())
+    }
+
+    fn has_min_remaining_validity_internal(&self, expiration: u64, now: u64) -> bool {
+        let required = now
+            .saturating_add(self.min_remaining_validity_ns)
+            .saturating_add(self.clock_skew_ns);
+        expiration >= required
+    }
+
+    /// Returns whether a delegation with the given expiration has expired,
+    /// tolerating the clock skew.
+    pub fn is_expired(&self, expiration: u64) -> bool {
+        self.is_expired_internal(expiration, time())
+    }
+
+    fn is_expired_internal(&self, expiration: u64, now: u64) -> bool {
+        expiration.saturating_add(self.clock_skew_ns) < now
+    }
+}
+
+/// A delegation together with the signature on it.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct SignedDelegation {
+    pub delegation: Delegation,
+    #[serde(with = "blob")]
+    pub signature: ByteBuf,
+}
+
+/// Checks that the given targets can be used to restrict a delegation, i.e. that there are
+/// between one and [MAX_DELEGATION_TARGETS] targets, without duplicates.
+pub fn validate_delegation_targets(targets: &[Principal]) -> Result<(), String> {
+    if targets.is_empty() {
+        return Err("empty delegation targets".to_string());
+    }
+    if targets.len() > MAX_DELEGATION_TARGETS {
+        return Err(format!(
+            "too many delegation targets: {}, at most {} allowed",
+            targets.len(),
+            MAX_DELEGATION_TARGETS
+        ));
+    }
+    let mut seen = HashSet::with_capacity(targets.len());
+    if let Some(duplicate) = targets.iter().find(|target| !seen.insert(*target)) {
+        return Err(format!("duplicate delegation target {}", duplicate));
+    }
+    Ok(())
+}
+
+/// Returns whether the given delegation authorizes calls to the given canister, i.e. whether
+/// it is unrestricted or the canister is one of its targets.
+///
+/// For calls to the management canister, the effective canister id must be checked.
+pub fn is_target_allowed(delegation: &Delegation, canister_id: &Principal) -> bool {
+    match &delegation.targets {
+        None => true,
+        Some(targets) => targets.contains(canister_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn canister_id(index: u64) -> Principal {
+        let mut bytes = index.to_be_bytes().to_vec();
+        bytes.extend([1, 1]);
+        Principal::from_slice(&bytes)
+    }
+
+    fn delegation(targets: Option<Vec<Principal>>) -> Delegation {
+        Delegation {
+            pubkey: ByteBuf::from(vec![1, 2, 3]),
+            expiration: 42,
+            targets,
+        }
+    }
+
+    #[test]
+    fn should_accept_valid_targets() {
+        let targets: Vec<_> = (0..MAX_DELEGATION_TARGETS as u64)
+            .map(canister_id)
+            .collect();
+        assert_eq!(validate_delegation_targets(&targets[..1]), Ok(()));
+        assert_eq!(validate_delegation_targets(&targets), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_invalid_targets() {
+        assert_matches!(
+            validate_delegation_targets(&[]),
+            Err(e) if e.contains("empty")
+        );
+        let targets: Vec<_> = (0..=MAX_DELEGATION_TARGETS as u64)
+            .map(canister_id)
+            .collect();
+        assert_matches!(
+            validate_delegation_targets(&targets),
+            Err(e) if e.contains("too many delegation targets: 1001")
+        );
+        assert_matches!(
+            validate_delegation_targets(&[canister_id(1), canister_id(2), canister_id(1)]),
+            Err(e) if e.contains(&format!("duplicate delegation target {}", canister_id(1)))
+        );
+    }
+
+    #[test]
+    fn should_check_targets() {
+        let unrestricted = delegation(None);
+        assert!(is_target_allowed(&unrestricted, &canister_id(1)));
+
+        let restricted = delegation(Some(vec![canister_id(1), canister_id(2)]));
+        assert!(is_target_allowed(&restricted, &canister_id(2)));
+        assert!(!is_target_allowed(&restricted, &canister_id(3)));
+    }
+
+    #[test]
+    fn should_compute_expiration() {
+        const NOW: u64 = 1_000 * HOUR_NS;
+        let policy = DelegationPolicy::default();
+        assert_eq!(
+            policy.expiration_internal(None, NOW),
+            Ok(NOW + policy.default_ttl_ns)
+        );
+        assert_eq!(
+            policy.expiration_internal(Some(HOUR_NS), NOW),
+            Ok(NOW + HOUR_NS)
+        );
+        assert_eq!(
+            policy.expiration_internal(Some(u64::MAX), NOW),
+            Ok(NOW + policy.max_ttl_ns)
+        );
+        assert_matches!(
+            policy.expiration_internal(Some(MINUTE_NS), NOW),
+            Err(e) if e.contains("too short")
+        );
+    }
+
+    #[test]
+    fn should_check_remaining_validity_and_expiry() {
+        const NOW: u64 = 1_000 * HOUR_NS;
+        let policy = DelegationPolicy {
+            min_remaining_validity_ns: 10,
+            clock_skew_ns: 5,
+            ..Default::default()
+        };
+        assert!(policy.has_min_remaining_validity_internal(NOW + 15, NOW));
+        assert!(!policy.has_min_remaining_validity_internal(NOW + 14, NOW));
+
+        assert!(!policy.is_expired_internal(NOW, NOW));
+        assert!(!policy.is_expired_internal(NOW - 5, NOW));
+        assert!(policy.is_expired_internal(NOW - 6, NOW));
+    }
+
+    #[test]
+    fn should_compute_signature_msg() {
+        let delegation = delegation(Some(vec![canister_id(1)]));
+        assert_eq!(
+            delegation.signature_msg(),
+            delegation_signature_msg(&[1, 2, 3], 42, Some(&[canister_id(1)]))
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! C bindings for the handling of canister signature public keys and signatures, so that
+//! services not written in Rust share the encoding with the canister.
+//!
+//! The header `include/ic_canister_sig_creation.h` is generated by the build with `cbindgen`,
+//! and the `cdylib` of the crate exports the functions declared there. The library is only built
+//! as an `rlib` by default, the `cdylib` is built with
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib`.
+//!
+//! The header is checked in, and compared against the generated one by the tests of this module;
+//! run them with the environment variable `UPDATE_C_HEADER` set to update it.
+//!
+//! Ownership rules:
+//! * Input buffers are borrowed for the duration of the call only. A null pointer is accepted
+//!   for an empty buffer (i.e. if the length is 0).
+//! * Buffers returned in an [IcBytes] are owned by the caller and must be released exactly once
+//!   with [ic_canister_sig_bytes_free]. They are only written if the call succeeds.
+//! * Fixed-size outputs (e.g. hashes) are written to caller-provided buffers.
+//! * If a call fails, the error message can be retrieved with [ic_canister_sig_last_error].
+use crate::{
+    delegation_signature_msg, hash_with_domain, parse_canister_sig_cbor, CanisterSigPublicKey,
+    SignatureDomain,
+};
+use candid::Principal;
+use std::cell::RefCell;
+
+/// The status of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum IcStatus {
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// The input could not be parsed or is invalid.
+    InvalidInput = 2,
+}
+
+/// A byte buffer owned by the caller, to be released with [ic_canister_sig_bytes_free].
+#[repr(C)]
+#[derive(Debug)]
+pub struct IcBytes {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// A borrowed byte buffer, e.g. an element of an array of inputs.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct IcSlice {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+impl IcBytes {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        IcBytes {
+            data: bytes as *mut u8,
+            len: bytes.len(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn fail(status: IcStatus, message: impl Into<String>) -> IcStatus {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message.into());
+    status
+}
+
+fn status(result: Result<(), (IcStatus, String)>) -> IcStatus {
+    match result {
+        Ok(()) => IcStatus::Ok,
+        Err((status, message)) => fail(status, message),
+    }
+}
+
+unsafe fn input<'a>(
+    data: *const u8,
+    len: usize,
+    name: &str,
+) -> Result<&'a [u8], (IcStatus, String)> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err((IcStatus::NullPointer, format!("{} is null", name))),
+        (false, _) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+unsafe fn output<'a, T>(out: *mut T, name: &str) -> Result<&'a mut T, (IcStatus, String)> {
+    out.as_mut()
+        .ok_or_else(|| (IcStatus::NullPointer, format!("{} is null", name)))
+}
+
+fn invalid(message: String) -> (IcStatus, String) {
+    (IcStatus::InvalidInput, message)
+}
+
+fn principal(bytes: &[u8]) -> Result<Principal, (IcStatus, String)> {
+    Principal::try_from_slice(bytes).map_err(|e| invalid(format!("invalid principal: {}", e)))
+}
+
+/// Releases a buffer returned by this library. Releasing an empty buffer (null data) is a no-op.
+///
+/// # Safety
+/// `bytes` must have been returned by this library and not been released before.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_bytes_free(bytes: IcBytes) {
+    if !bytes.data.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            bytes.data, bytes.len,
+        )));
+    }
+}
+
+/// Returns the message of the last error on the calling thread, as UTF-8 (not NUL-terminated).
+///
+/// # Safety
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_last_error(out: *mut IcBytes) -> IcStatus {
+    match out.as_mut() {
+        Some(out) => {
+            *out = IcBytes::from_vec(LAST_ERROR.with(|e| e.borrow().clone().into_bytes()));
+            IcStatus::Ok
+        }
+        None => IcStatus::NullPointer,
+    }
+}
+
+/// Computes the DER-encoding of the canister signature public key with the given
+/// canister id (as raw principal bytes) and seed.
+///
+/// # Safety
+/// The inputs must be valid for reads of the given lengths, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_pk_to_der(
+    canister_id: *const u8,
+    canister_id_len: usize,
+    seed: *const u8,
+    seed_len: usize,
+    out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let canister_id = principal(input(canister_id, canister_id_len, "canister_id")?)?;
+        let seed = input(seed, seed_len, "seed")?;
+        let out = output(out, "out")?;
+        *out = IcBytes::from_vec(CanisterSigPublicKey::new(canister_id, seed.to_vec()).to_der());
+        Ok(())
+    })())
+}
+
+/// Computes the raw encoding (without DER-envelope) of the canister signature public key with
+/// the given canister id (as raw principal bytes) and seed.
+///
+/// # Safety
+/// The inputs must be valid for reads of the given lengths, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_pk_to_raw(
+    canister_id: *const u8,
+    canister_id_len: usize,
+    seed: *const u8,
+    seed_len: usize,
+    out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let canister_id = principal(input(canister_id, canister_id_len, "canister_id")?)?;
+        let seed = input(seed, seed_len, "seed")?;
+        let out = output(out, "out")?;
+        *out = IcBytes::from_vec(CanisterSigPublicKey::new(canister_id, seed.to_vec()).to_raw());
+        Ok(())
+    })())
+}
+
+/// Parses a DER-encoded canister signature public key into its canister id
+/// (as raw principal bytes) and seed.
+///
+/// # Safety
+/// `pk_der` must be valid for reads of `pk_der_len` bytes, the outputs must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_pk_from_der(
+    pk_der: *const u8,
+    pk_der_len: usize,
+    canister_id_out: *mut IcBytes,
+    seed_out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let pk = CanisterSigPublicKey::try_from(input(pk_der, pk_der_len, "pk_der")?)
+            .map_err(invalid)?;
+        write_pk(pk, canister_id_out, seed_out)
+    })())
+}
+
+/// Parses a raw canister signature public key (without DER-envelope) into its canister id
+/// (as raw principal bytes) and seed.
+///
+/// # Safety
+/// `pk_raw` must be valid for reads of `pk_raw_len` bytes, the outputs must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_pk_from_raw(
+    pk_raw: *const u8,
+    pk_raw_len: usize,
+    canister_id_out: *mut IcBytes,
+    seed_out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let pk = CanisterSigPublicKey::try_from_raw(input(pk_raw, pk_raw_len, "pk_raw")?)
+            .map_err(invalid)?;
+        write_pk(pk, canister_id_out, seed_out)
+    })())
+}
+
+unsafe fn write_pk(
+    pk: CanisterSigPublicKey,
+    canister_id_out: *mut IcBytes,
+    seed_out: *mut IcBytes,
+) -> Result<(), (IcStatus, String)> {
+    let canister_id_out = output(canister_id_out, "canister_id_out")?;
+    let seed_out = output(seed_out, "seed_out")?;
+    *canister_id_out = IcBytes::from_vec(pk.canister_id.as_slice().to_vec());
+    *seed_out = IcBytes::from_vec(pk.seed);
+    Ok(())
+}
+
+/// Derives the self-authenticating principal of a DER-encoded canister signature public key,
+/// as raw principal bytes.
+///
+/// # Safety
+/// `pk_der` must be valid for reads of `pk_der_len` bytes, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_pk_to_principal(
+    pk_der: *const u8,
+    pk_der_len: usize,
+    out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let pk = CanisterSigPublicKey::try_from(input(pk_der, pk_der_len, "pk_der")?)
+            .map_err(invalid)?;
+        let out = output(out, "out")?;
+        *out = IcBytes::from_vec(pk.to_principal().as_slice().to_vec());
+        Ok(())
+    })())
+}
+
+/// Returns the textual representation of the given raw principal, as UTF-8 (not NUL-terminated).
+///
+/// # Safety
+/// `principal_bytes` must be valid for reads of `principal_len` bytes, `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_principal_to_text(
+    principal_bytes: *const u8,
+    principal_len: usize,
+    out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let principal = principal(input(principal_bytes, principal_len, "principal")?)?;
+        let out = output(out, "out")?;
+        *out = IcBytes::from_vec(principal.to_text().into_bytes());
+        Ok(())
+    })())
+}
+
+/// Computes the signing input of a delegation, see [delegation_signature_msg]. The targets are
+/// raw principals; if `targets` is null, the delegation is unrestricted.
+///
+/// # Safety
+/// `pubkey` must be valid for reads of `pubkey_len` bytes, `targets` (if not null) must be valid
+/// for reads of `targets_len` slices, each valid for reads of its length, and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_delegation_msg(
+    pubkey: *const u8,
+    pubkey_len: usize,
+    expiration: u64,
+    targets: *const IcSlice,
+    targets_len: usize,
+    out: *mut IcBytes,
+) -> IcStatus {
+    status((|| {
+        let pubkey = input(pubkey, pubkey_len, "pubkey")?;
+        let targets = match targets.is_null() {
+            true => None,
+            false => Some(
+                std::slice::from_raw_parts(targets, targets_len)
+                    .iter()
+                    .map(|target| principal(input(target.data, target.len, "target")?))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        let out = output(out, "out")?;
+        *out = IcBytes::from_vec(delegation_signature_msg(
+            pubkey,
+            expiration,
+            targets.as_deref(),
+        ));
+        Ok(())
+    })())
+}
+
+/// Hashes the given message with the given domain separator, see [hash_with_domain].
+///
+/// # Safety
+/// The inputs must be valid for reads of the given lengths, `out` must be valid for writes
+/// of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_hash_with_domain(
+    domain: *const u8,
+    domain_len: usize,
+    message: *const u8,
+    message_len: usize,
+    out: *mut [u8; 32],
+) -> IcStatus {
+    status((|| {
+        let domain = SignatureDomain::new(input(domain, domain_len, "domain")?).map_err(invalid)?;
+        let message = input(message, message_len, "message")?;
+        let out = output(out, "out")?;
+        *out = hash_with_domain(domain, message);
+        Ok(())
+    })())
+}
+
+/// Parses a CBOR-encoded canister signature, see [parse_canister_sig_cbor], and returns its
+/// CBOR-encoded certificate and the root hash of its tree.
+///
+/// # Safety
+/// `signature` must be valid for reads of `signature_len` bytes, `certificate_out` must be
+/// valid for writes and `tree_digest_out` must be valid for writes of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ic_canister_sig_parse(
+    signature: *const u8,
+    signature_len: usize,
+    certificate_out: *mut IcBytes,
+    tree_digest_out: *mut [u8; 32],
+) -> IcStatus {
+    status((|| {
+        let signature = parse_canister_sig_cbor(input(signature, signature_len, "signature")?)
+            .map_err(invalid)?;
+        let certificate_out = output(certificate_out, "certificate_out")?;
+        let tree_digest_out = output(tree_digest_out, "tree_digest_out")?;
+        *tree_digest_out = signature.tree().digest();
+        *certificate_out = IcBytes::from_vec(signature.certificate().to_vec());
+        Ok(())
+    })())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CanisterSig;
+
+    const CANISTER_ID: [u8; 10] = [0, 0, 0, 0, 0, 0, 0, 1, 1, 1];
+
+    #[test]
+    fn should_match_generated_header() {
+        let header = include_str!(concat!(env!("OUT_DIR"), "/ic_canister_sig_creation.h"));
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/include/ic_canister_sig_creation.h"
+        );
+        if std::env::var("UPDATE_C_HEADER").is_ok() {
+            std::fs::write(path, header).expect("failed to write C header");
+            return;
+        }
+        assert_eq!(
+            std::fs::read_to_string(path).expect("failed to read C header"),
+            header,
+            "C header is outdated, run the tests with UPDATE_C_HEADER set"
+        );
+    }
+
+    fn empty() -> IcBytes {
+        IcBytes {
+            data: std::ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Copies and releases the given buffer.
+    fn take(bytes: IcBytes) -> Vec<u8> {
+        let vec = unsafe { std::slice::from_raw_parts(bytes.data, bytes.len) }.to_vec();
+        unsafe { ic_canister_sig_bytes_free(bytes) };
+        vec
+    }
+
+    fn last_error() -> String {
+        let mut error = empty();
+        assert_eq!(
+            unsafe { ic_canister_sig_last_error(&mut error) },
+            IcStatus::Ok
+        );
+        String::from_utf8(take(error)).unwrap()
+    }
+
+    #[test]
+    fn should_encode_and_decode_keys() {
+        let key = CanisterSigPublicKey::new(Principal::from_slice(&CANISTER_ID), b"seed".to_vec());
+        let (mut der, mut raw) = (empty(), empty());
+        unsafe {
+            assert_eq!(
+                ic_canister_sig_pk_to_der(CANISTER_ID.as_ptr(), 10, b"seed".as_ptr(), 4, &mut der),
+                IcStatus::Ok
+            );
+            assert_eq!(
+                ic_canister_sig_pk_to_raw(CANISTER_ID.as_ptr(), 10, b"seed".as_ptr(), 4, &mut raw),
+                IcStatus::Ok
+            );
+        }
+        let (der, raw) = (take(der), take(raw));
+        assert_eq!(der, key.to_der());
+        assert_eq!(raw, key.to_raw());
+
+        let (mut canister_id, mut seed) = (empty(), empty());
+        let status = unsafe {
+            ic_canister_sig_pk_from_der(der.as_ptr(), der.len(), &mut canister_id, &mut seed)
+        };
+        assert_eq!(status, IcStatus::Ok);
+        assert_eq!(take(canister_id), CANISTER_ID);
+        assert_eq!(take(seed), b"seed");
+
+        let (mut canister_id, mut seed) = (empty(), empty());
+        let status = unsafe {
+            ic_canister_sig_pk_from_raw(raw.as_ptr(), raw.len(), &mut canister_id, &mut seed)
+        };
+        assert_eq!(status, IcStatus::Ok);
+        assert_eq!(take(canister_id), CANISTER_ID);
+        assert_eq!(take(seed), b"seed");
+
+        let (mut principal, mut text) = (empty(), empty());
+        unsafe {
+            assert_eq!(
+                ic_canister_sig_pk_to_principal(der.as_ptr(), der.len(), &mut principal),
+                IcStatus::Ok
+            );
+            let principal = take(principal);
+            assert_eq!(principal, key.to_principal().as_slice());
+            assert_eq!(
+                ic_canister_sig_principal_to_text(principal.as_ptr(), principal.len(), &mut text),
+                IcStatus::Ok
+            );
+        }
+        assert_eq!(take(text), key.to_principal().to_text().into_bytes());
+    }
+
+    #[test]
+    fn should_report_errors() {
+        let (mut canister_id, mut seed) = (empty(), empty());
+        let status =
+            unsafe { ic_canister_sig_pk_from_der([1, 2].as_ptr(), 2, &mut canister_id, &mut seed) };
+        assert_eq!(status, IcStatus::InvalidInput);
+        assert!(canister_id.data.is_null());
+        assert_eq!(last_error(), "canister sig pk shorter than DER prefix");
+
+        let status = unsafe {
+            ic_canister_sig_pk_from_der(std::ptr::null(), 2, &mut canister_id, &mut seed)
+        };
+        assert_eq!(status, IcStatus::NullPointer);
+        assert_eq!(last_error(), "pk_der is null");
+
+        let status = unsafe {
+            ic_canister_sig_pk_to_der(
+                CANISTER_ID.as_ptr(),
+                10,
+                std::ptr::null(),
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, IcStatus::NullPointer);
+        assert_eq!(last_error(), "out is null");
+    }
+
+    #[test]
+    fn should_compute_delegation_msg_and_hash() {
+        let targets = [IcSlice {
+            data: CANISTER_ID.as_ptr(),
+            len: CANISTER_ID.len(),
+        }];
+        let (mut restricted, mut unrestricted) = (empty(), empty());
+        unsafe {
+            assert_eq!(
+                ic_canister_sig_delegation_msg(
+                    b"pk".as_ptr(),
+                    2,
+                    42,
+                    targets.as_ptr(),
+                    1,
+                    &mut restricted
+                ),
+                IcStatus::Ok
+            );
+            assert_eq!(
+                ic_canister_sig_delegation_msg(
+                    b"pk".as_ptr(),
+                    2,
+                    42,
+                    std::ptr::null(),
+                    0,
+                    &mut unrestricted
+                ),
+                IcStatus::Ok
+            );
+        }
+        assert_eq!(
+            take(restricted),
+            delegation_signature_msg(b"pk", 42, Some(&[Principal::from_slice(&CANISTER_ID)]))
+        );
+        assert_eq!(
+            take(unrestricted),
+            delegation_signature_msg(b"pk", 42, None)
+        );
+
+        let mut hash = [0; 32];
+        let status = unsafe {
+            ic_canister_sig_hash_with_domain(
+                b"ic-request".as_ptr(),
+                10,
+                b"msg".as_ptr(),
+                3,
+                &mut hash,
+            )
+        };
+        assert_eq!(status, IcStatus::Ok);
+        assert_eq!(hash, hash_with_domain(SignatureDomain::IC_REQUEST, b"msg"));
+    }
+
+    #[test]
+    fn should_parse_signature() {
+        let tree = ic_certification::labeled(b"sig", ic_certification::leaf(b"value"));
+        let signature_cbor = CanisterSig::new(vec![1, 2, 3], tree.clone())
+            .to_cbor()
+            .unwrap();
+        let (mut certificate, mut tree_digest) = (empty(), [0; 32]);
+        let status = unsafe {
+            ic_canister_sig_parse(
+                signature_cbor.as_ptr(),
+                signature_cbor.len(),
+                &mut certificate,
+                &mut tree_digest,
+            )
+        };
+        assert_eq!(status, IcStatus::Ok);
+        assert_eq!(take(certificate), vec![1, 2, 3]);
+        assert_eq!(tree_digest, tree.digest());
+    }
+}
@@ -0,0 +1,307 @@
+//! Support for the ICRC-34 delegation standard, see
+//! https://github.com/dfinity/wg-identity-authentication/blob/main/topics/icrc_34_delegation.md
+//!
+//! A signer implementing ICRC-34 receives an `icrc34_delegation` request carrying a session public
+//! key, optional target canisters and an optional maximum time to live. It responds with a
+//! delegation from its canister signature public key to the session key:
+//! 1. In an update call, validate the request with [prepare_delegation], add the signature on
+//!    the delegation with [crate::delegation::add_delegation_signature] and update the
+//!    `certified_data` of the canister. Return the expiration of the delegation to the client.
+//! 2. In a subsequent query call, which receives the request together with that expiration,
+//!    reconstruct the delegation with [PreparedDelegation::new], retrieve the signature and
+//!    assemble the response with [PreparedDelegation::into_response]. Calling
+//!    [prepare_delegation] again would derive a different expiration from the current time,
+//!    so no signature would be found for it.
+//!
+//! The types can be used both with Candid and with the JSON-RPC encoding of ICRC-25, in which
+//! blobs are base64-encoded and 64-bit numbers are encoded as decimal strings. Fields are renamed
+//! individually (rather than with `rename_all`), as the Candid derivation only honors the former.
+use crate::delegation::{validate_delegation_targets, DelegationPolicy};
+use crate::serde_helpers::{blob, opt_nat64};
+use crate::session_key::SessionPublicKey;
+use crate::signature_map::CanisterSigInputs;
+use crate::{CanisterSigPublicKey, SignatureDomain};
+use candid::{CandidType, Principal};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+
+pub use crate::delegation::{Delegation, SignedDelegation};
+
+/// The parameters of an `icrc34_delegation` request.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct DelegationRequest {
+    /// The DER-encoded session public key to delegate to.
+    #[serde(rename = "publicKey", with = "blob")]
+    pub public_key: ByteBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<Vec<Principal>>,
+    /// The maximum time to live of the delegation, in nanoseconds.
+    #[serde(
+        rename = "maxTimeToLive",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "opt_nat64"
+    )]
+    pub max_time_to_live: Option<u64>,
+}
+
+/// The result of an `icrc34_delegation` request.
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Serialize, Deserialize)]
+pub struct DelegationResponse {
+    /// The DER-encoded public key of the signer, i.e. the start of the delegation chain.
+    #[serde(rename = "publicKey", with = "blob")]
+    pub public_key: ByteBuf,
+    #[serde(rename = "signerDelegation")]
+    pub signer_delegation: Vec<SignedDelegation>,
+}
+
+/// A validated delegation, waiting to be signed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PreparedDelegation {
+    pub delegation: Delegation,
+    /// The signing input of the delegation, cf. [Delegation::signature_msg].
+    pub message: Vec<u8>,
+}
+
+impl PreparedDelegation {
+    /// Constructs the delegation for the given request and expiration, e.g. in the query call
+    /// that retrieves the signature prepared by [prepare_delegation] in an earlier update call.
+    ///
+    /// The request is not validated again, as only a delegation that was validated by
+    /// [prepare_delegation] has a signature in the signature map.
+    pub fn new(request: &DelegationRequest, expiration: u64) -> Self {
+        let delegation = Delegation {
+            pubkey: request.public_key.clone(),
+            expiration,
+            targets: request.targets.clone(),
+        };
+        let message = delegation.signature_msg();
+        PreparedDelegation {
+            delegation,
+            message,
+        }
+    }
+
+    /// Returns the inputs of the canister signature on the delegation with the given seed.
+    pub fn sig_inputs<'a>(&'a self, seed: &'a [u8]) -> CanisterSigInputs<'a> {
+        CanisterSigInputs {
+            domain: SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+            seed,
+            message: &self.message,
+        }
+    }
+
+    /// Assembles the response with the given canister signature (CBOR-encoded)
+    /// of the signer's public key on the delegation.
+    pub fn into_response(
+        self,
+        signer_public_key: &CanisterSigPublicKey,
+        signature_cbor: Vec<u8>,
+    ) -> DelegationResponse {
+        DelegationResponse {
+            public_key: ByteBuf::from(signer_public_key.to_der()),
+            signer_delegation: vec![SignedDelegation {
+                delegation: self.delegation,
+                signature: ByteBuf::from(signature_cbor),
+            }],
+        }
+    }
+}
+
+/// Validates the given request and prepares the corresponding delegation.
+/// The session public key must be a valid key, see [SessionPublicKey].
+///
+/// The expiration of the delegation is determined by the given policy from the requested
+/// time to live, see [DelegationPolicy::expiration].
+pub fn prepare_delegation(
+    request: &DelegationRequest,
+    policy: &DelegationPolicy,
+) -> Result<PreparedDelegation, String> {
+    prepare_delegation_internal(request, policy, time())
+}
+
+fn prepare_delegation_internal(
+    request: &DelegationRequest,
+    policy: &DelegationPolicy,
+    now: u64,
+) -> Result<PreparedDelegation, String> {
+    SessionPublicKey::from_der(&request.public_key)?;
+    if let Some(targets) = &request.targets {
+        validate_delegation_targets(targets)?;
+    }
+    let expiration = policy.expiration_internal(request.max_time_to_live, now)?;
+    Ok(PreparedDelegation::new(request, expiration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delegation_signature_msg;
+    use assert_matches::assert_matches;
+    use candid::{Decode, Encode};
+
+    const NOW: u64 = 1_697_000_000_000_000_000;
+    const HOUR_NS: u64 = 60 * 60 * 1_000_000_000;
+    const ED25519_PK_DER: &[u8] = b"\x30\x2a\x30\x05\x06\x03\x2b\x65\x70\x03\x21\x00\xf2\xa8\xdc\x6a\x5b\x0b\xbe\x2b\x07\xdd\x8c\x0e\xbe\x6c\x3f\x4b\x4c\x1b\xf7\xcc\x2b\xc8\x6b\x4e\x0d\x3e\x0a\x70\xb7\xa1\x3e\x65";
+
+    fn request() -> DelegationRequest {
+        DelegationRequest {
+            public_key: ByteBuf::from(ED25519_PK_DER.to_vec()),
+            targets: Some(vec![Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1])]),
+            max_time_to_live: Some(HOUR_NS),
+        }
+    }
+
+    #[test]
+    fn should_prepare_delegation() {
+        let request = request();
+        let prepared =
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW).unwrap();
+        let expiration = NOW + HOUR_NS;
+        assert_eq!(
+            prepared.delegation,
+            Delegation {
+                pubkey: request.public_key.clone(),
+                expiration,
+                targets: request.targets.clone(),
+            }
+        );
+        assert_eq!(
+            prepared.message,
+            delegation_signature_msg(ED25519_PK_DER, expiration, request.targets.as_deref())
+        );
+        let inputs = prepared.sig_inputs(b"seed");
+        assert_eq!(inputs.domain, SignatureDomain::IC_REQUEST_AUTH_DELEGATION);
+        assert_eq!(inputs.message, prepared.message);
+    }
+
+    #[test]
+    fn should_reconstruct_prepared_delegation_from_expiration() {
+        let request = request();
+        let prepared =
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW).unwrap();
+        let reconstructed = PreparedDelegation::new(&request, prepared.delegation.expiration);
+        assert_eq!(reconstructed, prepared);
+    }
+
+    #[test]
+    fn should_clamp_time_to_live() {
+        let mut request = request();
+        request.max_time_to_live = Some(u64::MAX);
+        let prepared =
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW).unwrap();
+        assert_eq!(prepared.delegation.expiration, NOW + 8 * HOUR_NS);
+
+        request.max_time_to_live = None;
+        let prepared =
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW).unwrap();
+        assert_eq!(prepared.delegation.expiration, NOW + HOUR_NS / 2);
+    }
+
+    #[test]
+    fn should_reject_invalid_requests() {
+        let mut request = request();
+        request.public_key = ByteBuf::from(vec![1, 2, 3]);
+        assert_matches!(
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW),
+            Err(e) if e.contains("invalid session pk DER")
+        );
+
+        let mut request = self::request();
+        request.targets = Some(vec![]);
+        assert_matches!(
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW),
+            Err(e) if e.contains("empty delegation targets")
+        );
+
+        let mut request = self::request();
+        request.max_time_to_live = Some(0);
+        assert_matches!(
+            prepare_delegation_internal(&request, &DelegationPolicy::default(), NOW),
+            Err(e) if e.contains("time to live too short")
+        );
+    }
+
+    #[test]
+    fn should_assemble_response() {
+        let signer = CanisterSigPublicKey::new(
+            Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]),
+            vec![42],
+        );
+        let prepared =
+            prepare_delegation_internal(&request(), &DelegationPolicy::default(), NOW).unwrap();
+        let delegation = prepared.delegation.clone();
+        let response = prepared.into_response(&signer, vec![0xd9, 0xd9, 0xf7]);
+        assert_eq!(response.public_key.as_slice(), signer.to_der());
+        assert_eq!(
+            response.signer_delegation,
+            vec![SignedDelegation {
+                delegation,
+                signature: ByteBuf::from(vec![0xd9, 0xd9, 0xf7]),
+            }]
+        );
+    }
+
+    #[test]
+    fn should_round_trip_candid() {
+        let request = request();
+        let request_candid = Encode!(&request).unwrap();
+        assert_eq!(
+            Decode!(&request_candid, DelegationRequest).unwrap(),
+            request
+        );
+
+        let response = DelegationResponse {
+            public_key: ByteBuf::from(vec![4, 5]),
+            signer_delegation: vec![SignedDelegation {
+                delegation: Delegation {
+                    pubkey: ByteBuf::from(vec![1, 2, 3]),
+                    expiration: NOW,
+                    targets: None,
+                },
+                signature: ByteBuf::from(vec![6]),
+            }],
+        };
+        let response_candid = Encode!(&response).unwrap();
+        assert_eq!(
+            Decode!(&response_candid, DelegationResponse).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn should_use_icrc25_json_encoding() {
+        let request_json = r#"{"publicKey":"AQID","targets":["rwlgt-iiaaa-aaaaa-aaaaa-cai"],"maxTimeToLive":"60000000000"}"#;
+        let request: DelegationRequest = serde_json::from_str(request_json).unwrap();
+        assert_eq!(request.public_key.as_slice(), [1, 2, 3]);
+        assert_eq!(
+            request.targets,
+            Some(vec![
+                Principal::from_text("rwlgt-iiaaa-aaaaa-aaaaa-cai").unwrap()
+            ])
+        );
+        assert_eq!(request.max_time_to_live, Some(60_000_000_000));
+        assert_eq!(serde_json::to_string(&request).unwrap(), request_json);
+
+        let minimal: DelegationRequest = serde_json::from_str(r#"{"publicKey":"AQID"}"#).unwrap();
+        assert_eq!(minimal.targets, None);
+        assert_eq!(minimal.max_time_to_live, None);
+
+        let response = DelegationResponse {
+            public_key: ByteBuf::from(vec![4, 5]),
+            signer_delegation: vec![SignedDelegation {
+                delegation: Delegation {
+                    pubkey: ByteBuf::from(vec![1, 2, 3]),
+                    expiration: NOW,
+                    targets: None,
+                },
+                signature: ByteBuf::from(vec![6]),
+            }],
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"publicKey":"BAU=","signerDelegation":[{"delegation":{"pubkey":"AQID","expiration":"1697000000000000000"},"signature":"Bg=="}]}"#
+        );
+    }
+}
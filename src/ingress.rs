@@ -0,0 +1,438 @@
+//! Canister signatures on IC ingress requests.
+//!
+//! Besides signing delegations, a canister signature can directly sign the request id of an
+//! ingress message (call, query or read_state) under the [SignatureDomain::IC_REQUEST] domain.
+//! The sender of such a request is the self-authenticating principal of the canister signature
+//! public key (cf. [CanisterSigPublicKey::to_principal]), so a canister-controlled identity can
+//! submit requests without an intermediate session key:
+//! 1. In an update call, register the request with [add_request_signature] and update the
+//!    `certified_data` of the canister with the new [SignatureMap::root_hash].
+//! 2. In a subsequent query call, assemble the authenticated envelope with [signed_envelope]
+//!    and hand its [Envelope::to_cbor] encoding to the client for submission.
+//!
+//! See https://internetcomputer.org/docs/current/references/ic-interface-spec#authentication
+use crate::delegation::SignedDelegation;
+use crate::signature_map::{AddedSignature, CanisterSigError, CanisterSigInputs, SignatureMap};
+use crate::{cbor, CanisterSigPublicKey, SignatureDomain};
+use candid::Principal;
+use ic_certification::Hash;
+use ic_representation_independent_hash::{representation_independent_hash, Value};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use thiserror::Error;
+
+/// The content of an IC ingress request, see
+/// https://internetcomputer.org/docs/current/references/ic-interface-spec#http-interface
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "request_type", rename_all = "snake_case")]
+pub enum EnvelopeContent {
+    Call {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<ByteBuf>,
+        ingress_expiry: u64,
+        #[serde(with = "principal_bytes")]
+        sender: Principal,
+        #[serde(with = "principal_bytes")]
+        canister_id: Principal,
+        method_name: String,
+        arg: ByteBuf,
+    },
+    Query {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<ByteBuf>,
+        ingress_expiry: u64,
+        #[serde(with = "principal_bytes")]
+        sender: Principal,
+        #[serde(with = "principal_bytes")]
+        canister_id: Principal,
+        method_name: String,
+        arg: ByteBuf,
+    },
+    ReadState {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<ByteBuf>,
+        ingress_expiry: u64,
+        #[serde(with = "principal_bytes")]
+        sender: Principal,
+        paths: Vec<Vec<ByteBuf>>,
+    },
+}
+
+impl EnvelopeContent {
+    /// Returns the sender of the request.
+    pub fn sender(&self) -> &Principal {
+        match self {
+            EnvelopeContent::Call { sender, .. }
+            | EnvelopeContent::Query { sender, .. }
+            | EnvelopeContent::ReadState { sender, .. } => sender,
+        }
+    }
+
+    /// Returns the id of the canister targeted by a call or query, or `None` for a read_state
+    /// request, whose effective canister id is not part of the content.
+    pub fn canister_id(&self) -> Option<&Principal> {
+        match self {
+            EnvelopeContent::Call { canister_id, .. }
+            | EnvelopeContent::Query { canister_id, .. } => Some(canister_id),
+            EnvelopeContent::ReadState { .. } => None,
+        }
+    }
+
+    /// Returns the expiry of the request, in nanoseconds since the UNIX epoch.
+    pub fn ingress_expiry(&self) -> u64 {
+        match self {
+            EnvelopeContent::Call { ingress_expiry, .. }
+            | EnvelopeContent::Query { ingress_expiry, .. }
+            | EnvelopeContent::ReadState { ingress_expiry, .. } => *ingress_expiry,
+        }
+    }
+
+    /// Computes the request id, i.e. the representation-independent hash of the content, see
+    /// https://internetcomputer.org/docs/current/references/ic-interface-spec#request-id
+    pub fn request_id(&self) -> Hash {
+        let mut m: Vec<(String, Value)> = vec![];
+        let nonce = match self {
+            EnvelopeContent::Call {
+                nonce,
+                canister_id,
+                method_name,
+                arg,
+                ..
+            }
+            | EnvelopeContent::Query {
+                nonce,
+                canister_id,
+                method_name,
+                arg,
+                ..
+            } => {
+                let request_type = match self {
+                    EnvelopeContent::Call { .. } => "call",
+                    _ => "query",
+                };
+                m.push(("request_type".into(), Value::String(request_type.into())));
+                m.push((
+                    "canister_id".into(),
+                    Value::Bytes(canister_id.as_slice().to_vec()),
+                ));
+                m.push(("method_name".into(), Value::String(method_name.clone())));
+                m.push(("arg".into(), Value::Bytes(arg.to_vec())));
+                nonce
+            }
+            EnvelopeContent::ReadState { nonce, paths, .. } => {
+                m.push(("request_type".into(), Value::String("read_state".into())));
+                let paths = paths
+                    .iter()
+                    .map(|path| {
+                        Value::Array(
+                            path.iter()
+                                .map(|label| Value::Bytes(label.to_vec()))
+                                .collect(),
+                        )
+                    })
+                    .collect();
+                m.push(("paths".into(), Value::Array(paths)));
+                nonce
+            }
+        };
+        if let Some(nonce) = nonce {
+            m.push(("nonce".into(), Value::Bytes(nonce.to_vec())));
+        }
+        m.push((
+            "ingress_expiry".into(),
+            Value::Number(self.ingress_expiry()),
+        ));
+        m.push((
+            "sender".into(),
+            Value::Bytes(self.sender().as_slice().to_vec()),
+        ));
+        representation_independent_hash(m.as_slice())
+    }
+}
+
+/// (De)serializes principals as CBOR byte strings. The [Principal] implementation cannot be used
+/// within the internally tagged [EnvelopeContent], as it rejects buffered byte strings.
+mod principal_bytes {
+    use candid::Principal;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use serde_bytes::ByteBuf;
+
+    pub fn serialize<S: Serializer>(
+        principal: &Principal,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(principal.as_slice())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Principal, D::Error> {
+        let bytes = ByteBuf::deserialize(deserializer)?;
+        Principal::try_from_slice(&bytes).map_err(de::Error::custom)
+    }
+}
+
+/// An authenticated IC ingress request, see
+/// https://internetcomputer.org/docs/current/references/ic-interface-spec#authentication
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    pub content: EnvelopeContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_pubkey: Option<ByteBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_sig: Option<ByteBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_delegation: Option<Vec<SignedDelegation>>,
+}
+
+impl Envelope {
+    /// Constructs an envelope for the given content, authenticated directly (i.e. without
+    /// delegations) by the canister signature `signature_cbor` of the given public key.
+    pub fn new_canister_signed(
+        content: EnvelopeContent,
+        public_key: &CanisterSigPublicKey,
+        signature_cbor: Vec<u8>,
+    ) -> Self {
+        Envelope {
+            content,
+            sender_pubkey: Some(ByteBuf::from(public_key.to_der())),
+            sender_sig: Some(ByteBuf::from(signature_cbor)),
+            sender_delegation: None,
+        }
+    }
+
+    /// Returns the CBOR-encoding of this envelope, prefixed with the self-describing CBOR tag,
+    /// as expected by the HTTP interface of the IC.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, String> {
+        cbor::to_vec_self_describing(self)
+            .map_err(|e| format!("failed to encode envelope CBOR: {}", e))
+    }
+
+    /// Parses the given bytes as a CBOR-encoded envelope.
+    pub fn from_cbor(envelope_cbor: &[u8]) -> Result<Self, String> {
+        cbor::from_slice(envelope_cbor).map_err(|e| format!("failed to parse envelope CBOR: {}", e))
+    }
+}
+
+/// Errors of [add_request_signature] and [signed_envelope].
+#[derive(Error, Debug)]
+pub enum IngressSigError {
+    #[error(
+        "The request sender {actual} does not match the principal {expected} of the signing key."
+    )]
+    SenderMismatch {
+        expected: Principal,
+        actual: Principal,
+    },
+    #[error(transparent)]
+    Signature(#[from] CanisterSigError),
+}
+
+/// Returns the inputs of the canister signature on the given request id under the given seed.
+pub fn request_sig_inputs<'a>(seed: &'a [u8], request_id: &'a Hash) -> CanisterSigInputs<'a> {
+    CanisterSigInputs {
+        domain: SignatureDomain::IC_REQUEST,
+        seed,
+        message: request_id,
+    }
+}
+
+/// Adds the signature on the request id of `content` for the seed of `public_key` to the map.
+///
+/// Fails with [IngressSigError::SenderMismatch] if the sender of the request is not
+/// the principal of `public_key`, as the IC would reject such a request.
+pub fn add_request_signature(
+    signatures: &mut SignatureMap,
+    public_key: &CanisterSigPublicKey,
+    content: &EnvelopeContent,
+) -> Result<AddedSignature, IngressSigError> {
+    check_sender(public_key, content)?;
+    let request_id = content.request_id();
+    Ok(signatures.add_signature(&request_sig_inputs(&public_key.seed, &request_id))?)
+}
+
+/// Assembles the authenticated envelope for `content`, which must have been registered with
+/// [add_request_signature] before. Must be called in a query call, as it requires the data
+/// certificate of the canister (cf. [SignatureMap::get_signature_as_cbor]).
+pub fn signed_envelope(
+    signatures: &SignatureMap,
+    public_key: &CanisterSigPublicKey,
+    content: EnvelopeContent,
+) -> Result<Envelope, IngressSigError> {
+    check_sender(public_key, &content)?;
+    let request_id = content.request_id();
+    let signature_cbor = signatures
+        .get_signature_as_cbor(&request_sig_inputs(&public_key.seed, &request_id), None)?;
+    Ok(Envelope::new_canister_signed(
+        content,
+        public_key,
+        signature_cbor,
+    ))
+}
+
+fn check_sender(
+    public_key: &CanisterSigPublicKey,
+    content: &EnvelopeContent,
+) -> Result<(), IngressSigError> {
+    let expected = public_key.to_principal();
+    if *content.sender() != expected {
+        return Err(IngressSigError::SenderMismatch {
+            expected,
+            actual: *content.sender(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn public_key() -> CanisterSigPublicKey {
+        CanisterSigPublicKey::new(
+            Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]),
+            vec![42],
+        )
+    }
+
+    fn call_content(sender: Principal) -> EnvelopeContent {
+        EnvelopeContent::Call {
+            nonce: None,
+            ingress_expiry: 1_685_570_400_000_000_000,
+            sender,
+            canister_id: Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0x04, 0xd2]),
+            method_name: "hello".to_string(),
+            arg: ByteBuf::from(b"DIDL\x00\xfd*".to_vec()),
+        }
+    }
+
+    #[test]
+    fn should_compute_call_request_id() {
+        // Example from the interface specification.
+        let content = call_content(Principal::anonymous());
+        assert_eq!(
+            hex::encode(content.request_id()),
+            "1d1091364d6bb8a6c16b203ee75467d59ead468f523eb058880ae8ec80e2b101"
+        );
+    }
+
+    #[test]
+    fn should_distinguish_request_types_and_nonce() {
+        let call = call_content(Principal::anonymous());
+        let EnvelopeContent::Call {
+            ingress_expiry,
+            sender,
+            canister_id,
+            method_name,
+            arg,
+            ..
+        } = call.clone()
+        else {
+            unreachable!()
+        };
+        let query = EnvelopeContent::Query {
+            nonce: None,
+            ingress_expiry,
+            sender,
+            canister_id,
+            method_name: method_name.clone(),
+            arg: arg.clone(),
+        };
+        let call_with_nonce = EnvelopeContent::Call {
+            nonce: Some(ByteBuf::from(vec![1, 2, 3])),
+            ingress_expiry,
+            sender,
+            canister_id,
+            method_name,
+            arg,
+        };
+        assert_ne!(call.request_id(), query.request_id());
+        assert_ne!(call.request_id(), call_with_nonce.request_id());
+    }
+
+    #[test]
+    fn should_compute_read_state_request_id() {
+        let content = EnvelopeContent::ReadState {
+            nonce: None,
+            ingress_expiry: 1_685_570_400_000_000_000,
+            sender: Principal::anonymous(),
+            paths: vec![vec![
+                ByteBuf::from(b"request_status".to_vec()),
+                ByteBuf::from(vec![1; 32]),
+            ]],
+        };
+        let paths = Value::Array(vec![Value::Array(vec![
+            Value::Bytes(b"request_status".to_vec()),
+            Value::Bytes(vec![1; 32]),
+        ])]);
+        let expected = representation_independent_hash(&[
+            ("request_type".into(), Value::String("read_state".into())),
+            ("paths".into(), paths),
+            (
+                "ingress_expiry".into(),
+                Value::Number(1_685_570_400_000_000_000),
+            ),
+            ("sender".into(), Value::Bytes(vec![4])),
+        ]);
+        assert_eq!(content.request_id(), expected);
+    }
+
+    #[test]
+    fn should_use_request_domain() {
+        let request_id = [7; 32];
+        let inputs = request_sig_inputs(b"seed", &request_id);
+        assert_eq!(inputs.domain, SignatureDomain::IC_REQUEST);
+        assert_eq!(inputs.seed, b"seed");
+        assert_eq!(inputs.message, request_id);
+    }
+
+    #[test]
+    fn should_round_trip_envelope_cbor() {
+        let public_key = public_key();
+        let envelope = Envelope::new_canister_signed(
+            call_content(public_key.to_principal()),
+            &public_key,
+            vec![0xd9, 0xd9, 0xf7, 0xa0],
+        );
+        let envelope_cbor = envelope.to_cbor().expect("failed to encode envelope");
+        assert!(envelope_cbor.starts_with(&cbor::SELF_DESCRIBING_TAG));
+        assert_eq!(Envelope::from_cbor(&envelope_cbor), Ok(envelope));
+    }
+
+    #[test]
+    fn should_encode_content_with_request_type() {
+        let content = call_content(Principal::anonymous());
+        let content_cbor = cbor::to_vec(&content).unwrap();
+        let cbor::Value::Map(fields) = cbor::from_slice::<cbor::Value>(&content_cbor).unwrap()
+        else {
+            panic!("content is not a CBOR map");
+        };
+        assert_eq!(
+            fields[0],
+            (
+                cbor::Value::Text("request_type".into()),
+                cbor::Value::Text("call".into())
+            )
+        );
+        assert!(fields.contains(&(
+            cbor::Value::Text("sender".into()),
+            cbor::Value::Bytes(vec![4])
+        )));
+    }
+
+    #[test]
+    fn should_reject_mismatching_sender() {
+        let mut signatures = SignatureMap::default();
+        let public_key = public_key();
+        let result = add_request_signature(
+            &mut signatures,
+            &public_key,
+            &call_content(Principal::anonymous()),
+        );
+        assert_matches!(
+            result,
+            Err(IngressSigError::SenderMismatch { expected, actual })
+                if expected == public_key.to_principal() && actual == Principal::anonymous()
+        );
+        assert!(signatures.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! JavaScript bindings, so that frontends can share the encoding of keys and signatures
+//! with the canister instead of re-implementing it.
+//!
+//! The library itself is only built as an `rlib`, so the package is built as a `cdylib` with
+//! `cargo rustc` and then processed with `wasm-bindgen`, e.g. for Node:
+//! ```shell
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --features js --crate-type cdylib
+//! wasm-bindgen --target nodejs --out-dir pkg target/wasm32-unknown-unknown/release/ic_canister_sig_creation.wasm
+//! ```
+//! The bindings are tested under Node with `wasm-pack test --node --features js --test js`.
+//! Byte arrays are passed as `Uint8Array`s, principals as text and 64-bit numbers as `BigInt`s.
+//! Errors are thrown as `Error`s with the message of the underlying Rust error.
+use crate::{CanisterSig, CanisterSigPublicKey};
+use candid::Principal;
+use wasm_bindgen::prelude::*;
+
+/// A canister signature public key, cf. [CanisterSigPublicKey].
+#[wasm_bindgen(js_name = CanisterSigPublicKey)]
+pub struct JsCanisterSigPublicKey(CanisterSigPublicKey);
+
+#[wasm_bindgen(js_class = CanisterSigPublicKey)]
+impl JsCanisterSigPublicKey {
+    /// Constructs the key of the given canister (as text) with the given seed.
+    #[wasm_bindgen(constructor)]
+    pub fn new(canister_id: &str, seed: &[u8]) -> Result<JsCanisterSigPublicKey, JsError> {
+        let canister_id = parse_principal(canister_id)?;
+        Ok(JsCanisterSigPublicKey(CanisterSigPublicKey::new(
+            canister_id,
+            seed.to_vec(),
+        )))
+    }
+
+    #[wasm_bindgen(js_name = fromDer)]
+    pub fn from_der(pk_der: &[u8]) -> Result<JsCanisterSigPublicKey, JsError> {
+        CanisterSigPublicKey::try_from(pk_der)
+            .map(JsCanisterSigPublicKey)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    #[wasm_bindgen(js_name = fromRaw)]
+    pub fn from_raw(pk_raw: &[u8]) -> Result<JsCanisterSigPublicKey, JsError> {
+        CanisterSigPublicKey::try_from_raw(pk_raw)
+            .map(JsCanisterSigPublicKey)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    #[wasm_bindgen(js_name = fromCose)]
+    pub fn from_cose(pk_cose: &[u8]) -> Result<JsCanisterSigPublicKey, JsError> {
+        CanisterSigPublicKey::from_cose(pk_cose)
+            .map(JsCanisterSigPublicKey)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    #[wasm_bindgen(js_name = fromPem)]
+    pub fn from_pem(pk_pem: &str) -> Result<JsCanisterSigPublicKey, JsError> {
+        CanisterSigPublicKey::from_pem(pk_pem)
+            .map(JsCanisterSigPublicKey)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    #[wasm_bindgen(js_name = fromBase64url)]
+    pub fn from_base64url(pk_base64url: &str) -> Result<JsCanisterSigPublicKey, JsError> {
+        CanisterSigPublicKey::from_base64url(pk_base64url)
+            .map(JsCanisterSigPublicKey)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// The canister id of the key, as text.
+    #[wasm_bindgen(getter, js_name = canisterId)]
+    pub fn canister_id(&self) -> String {
+        self.0.canister_id.to_text()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seed(&self) -> Vec<u8> {
+        self.0.seed.clone()
+    }
+
+    /// Returns the self-authenticating principal of the key, as text.
+    #[wasm_bindgen(js_name = toPrincipal)]
+    pub fn to_principal(&self) -> String {
+        self.0.to_principal().to_text()
+    }
+
+    #[wasm_bindgen(js_name = toDer)]
+    pub fn to_der(&self) -> Vec<u8> {
+        self.0.to_der()
+    }
+
+    #[wasm_bindgen(js_name = toRaw)]
+    pub fn to_raw(&self) -> Vec<u8> {
+        self.0.to_raw()
+    }
+
+    #[wasm_bindgen(js_name = toCose)]
+    pub fn to_cose(&self) -> Vec<u8> {
+        self.0.to_cose()
+    }
+
+    #[wasm_bindgen(js_name = toPem)]
+    pub fn to_pem(&self) -> String {
+        self.0.to_pem()
+    }
+
+    #[wasm_bindgen(js_name = toBase64url)]
+    pub fn to_base64url(&self) -> String {
+        self.0.to_base64url()
+    }
+}
+
+/// A parsed canister signature, cf. [CanisterSig].
+#[wasm_bindgen(js_name = CanisterSig)]
+pub struct JsCanisterSig(CanisterSig);
+
+#[wasm_bindgen(js_class = CanisterSig)]
+impl JsCanisterSig {
+    /// Parses a CBOR-encoded canister signature, cf. [crate::parse_canister_sig_cbor].
+    pub fn parse(signature_cbor: &[u8]) -> Result<JsCanisterSig, JsError> {
+        crate::parse_canister_sig_cbor(signature_cbor)
+            .map(JsCanisterSig)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// The CBOR-encoded certificate of the signature.
+    #[wasm_bindgen(getter)]
+    pub fn certificate(&self) -> Vec<u8> {
+        self.0.certificate().to_vec()
+    }
+
+    /// Returns the root hash of the tree of the signature, which must match the
+    /// `certified_data` of the canister in the certificate.
+    #[wasm_bindgen(js_name = treeDigest)]
+    pub fn tree_digest(&self) -> Vec<u8> {
+        self.0.tree().digest().to_vec()
+    }
+
+    /// Returns a human-readable rendering of the tree of the signature.
+    #[wasm_bindgen(js_name = treeToString)]
+    pub fn tree_to_string(&self) -> String {
+        format!("{:#?}", self.0.tree())
+    }
+
+    #[wasm_bindgen(js_name = toCbor)]
+    pub fn to_cbor(&self) -> Result<Vec<u8>, JsError> {
+        self.0.to_cbor().map_err(|e| JsError::new(&e))
+    }
+}
+
+/// Computes the signing input of a delegation, cf. [crate::delegation_signature_msg].
+/// The targets are given as text, `undefined` for an unrestricted delegation.
+#[wasm_bindgen(js_name = delegationSignatureMsg)]
+pub fn delegation_signature_msg(
+    pubkey: &[u8],
+    expiration: u64,
+    targets: Option<Vec<String>>,
+) -> Result<Vec<u8>, JsError> {
+    let targets = targets
+        .map(|targets| {
+            targets
+                .iter()
+                .map(|target| parse_principal(target))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    Ok(crate::delegation_signature_msg(
+        pubkey,
+        expiration,
+        targets.as_deref(),
+    ))
+}
+
+/// Returns the SHA-256 hash of the given bytes, e.g. of a seed, cf. [crate::hash_bytes].
+#[wasm_bindgen(js_name = hashBytes)]
+pub fn hash_bytes(bytes: &[u8]) -> Vec<u8> {
+    crate::hash_bytes(bytes).to_vec()
+}
+
+fn parse_principal(text: &str) -> Result<Principal, JsError> {
+    Principal::from_text(text).map_err(|e| JsError::new(&format!("invalid principal: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANISTER_ID: &str = "rwlgt-iiaaa-aaaaa-aaaaa-cai";
+
+    #[test]
+    fn should_encode_keys_as_the_library() {
+        let js_key = JsCanisterSigPublicKey::new(CANISTER_ID, b"seed").unwrap();
+        let key =
+            CanisterSigPublicKey::new(Principal::from_text(CANISTER_ID).unwrap(), b"seed".to_vec());
+        assert_eq!(js_key.canister_id(), CANISTER_ID);
+        assert_eq!(js_key.seed(), b"seed");
+        assert_eq!(js_key.to_principal(), key.to_principal().to_text());
+        assert_eq!(js_key.to_der(), key.to_der());
+        assert_eq!(js_key.to_raw(), key.to_raw());
+        assert_eq!(js_key.to_cose(), key.to_cose());
+        assert_eq!(js_key.to_pem(), key.to_pem());
+        assert_eq!(js_key.to_base64url(), key.to_base64url());
+    }
+
+    #[test]
+    fn should_round_trip_keys() {
+        let key = JsCanisterSigPublicKey::new(CANISTER_ID, b"seed").unwrap();
+        let der = key.to_der();
+        assert_eq!(
+            JsCanisterSigPublicKey::from_der(&der).unwrap().to_der(),
+            der
+        );
+        assert_eq!(
+            JsCanisterSigPublicKey::from_raw(&key.to_raw())
+                .unwrap()
+                .to_der(),
+            der
+        );
+        assert_eq!(
+            JsCanisterSigPublicKey::from_cose(&key.to_cose())
+                .unwrap()
+                .to_der(),
+            der
+        );
+        assert_eq!(
+            JsCanisterSigPublicKey::from_pem(&key.to_pem())
+                .unwrap()
+                .to_der(),
+            der
+        );
+        assert_eq!(
+            JsCanisterSigPublicKey::from_base64url(&key.to_base64url())
+                .unwrap()
+                .to_der(),
+            der
+        );
+    }
+
+    #[test]
+    fn should_compute_delegation_signature_msg_as_the_library() {
+        let target = Principal::from_text(CANISTER_ID).unwrap();
+        assert_eq!(
+            delegation_signature_msg(b"pubkey", 42, Some(vec![CANISTER_ID.to_string()])).unwrap(),
+            crate::delegation_signature_msg(b"pubkey", 42, Some(&[target]))
+        );
+        assert_eq!(
+            delegation_signature_msg(b"pubkey", 42, None).unwrap(),
+            crate::delegation_signature_msg(b"pubkey", 42, None)
+        );
+    }
+
+    #[test]
+    fn should_inspect_canister_sig() {
+        let tree = ic_certification::labeled(b"sig", ic_certification::leaf(b"value"));
+        let sig = CanisterSig::new(vec![1, 2, 3], tree.clone());
+        let js_sig = JsCanisterSig::parse(&sig.to_cbor().unwrap()).unwrap();
+        assert_eq!(js_sig.certificate(), vec![1, 2, 3]);
+        assert_eq!(js_sig.tree_digest(), tree.digest().to_vec());
+        assert!(js_sig.tree_to_string().contains("sig"));
+        assert_eq!(js_sig.to_cbor().unwrap(), sig.to_cbor().unwrap());
+    }
+}
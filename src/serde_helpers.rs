@@ -0,0 +1,80 @@
+//! Serde helpers for types that are used both in human-readable formats (e.g. the JSON-RPC
+//! encoding of ICRC-25) and in binary formats (e.g. Candid or CBOR).
+/// (De)serializes blobs as base64 strings in human-readable formats (e.g. JSON),
+/// and as byte strings otherwise (e.g. Candid).
+pub(crate) mod blob {
+    use base64::prelude::{Engine, BASE64_STANDARD};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use serde_bytes::ByteBuf;
+
+    pub fn serialize<S: Serializer>(blob: &ByteBuf, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&BASE64_STANDARD.encode(blob))
+        } else {
+            blob.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ByteBuf, D::Error> {
+        if deserializer.is_human_readable() {
+            let blob_base64 = String::deserialize(deserializer)?;
+            BASE64_STANDARD
+                .decode(blob_base64)
+                .map(ByteBuf::from)
+                .map_err(de::Error::custom)
+        } else {
+            ByteBuf::deserialize(deserializer)
+        }
+    }
+}
+
+/// (De)serializes 64-bit numbers as decimal strings in human-readable formats (e.g. JSON),
+/// as JSON numbers cannot represent them precisely in general.
+pub(crate) mod nat64 {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&value.to_string())
+        } else {
+            serializer.serialize_u64(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(de::Error::custom)
+        } else {
+            u64::deserialize(deserializer)
+        }
+    }
+}
+
+/// Like [nat64], for optional numbers.
+pub(crate) mod opt_nat64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) if serializer.is_human_readable() => {
+                serializer.serialize_some(&value.to_string())
+            }
+            Some(value) => serializer.serialize_some(value),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        if deserializer.is_human_readable() {
+            Option::<String>::deserialize(deserializer)?
+                .map(|value| value.parse().map_err(serde::de::Error::custom))
+                .transpose()
+        } else {
+            Option::<u64>::deserialize(deserializer)
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! Validation of session public keys, i.e. the keys that delegations are issued to.
+//!
+//! The IC accepts DER-encoded Ed25519, ECDSA P-256 and secp256k1 keys, DER-wrapped COSE keys
+//! (as used by WebAuthn, with ES256 or RS256 only) and canister signature public keys, see
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec#signatures
+//!
+//! The keys are checked structurally (algorithm identifiers, lengths and point encodings),
+//! but it is not checked that the points are on the respective curve.
+use crate::{cbor, CanisterSigPublicKey, CANISTER_SIG_PK_DER_OID, COSE_LABEL_ALG, COSE_LABEL_KTY};
+use std::fmt;
+
+const DER_TAG_SEQUENCE: u8 = 0x30;
+const DER_TAG_BIT_STRING: u8 = 0x03;
+const DER_TAG_OID: u8 = 0x06;
+
+// Contents of the DER-encoded object identifiers.
+const OID_ED25519: &[u8] = b"\x2b\x65\x70"; // 1.3.101.112
+const OID_EC_PUBLIC_KEY: &[u8] = b"\x2a\x86\x48\xce\x3d\x02\x01"; // 1.2.840.10045.2.1
+const OID_P256: &[u8] = b"\x2a\x86\x48\xce\x3d\x03\x01\x07"; // 1.2.840.10045.3.1.7
+const OID_SECP256K1: &[u8] = b"\x2b\x81\x04\x00\x0a"; // 1.3.132.0.10
+const OID_IC_COSE: &[u8] = b"\x2b\x06\x01\x04\x01\x83\xb8\x43\x01\x01"; // 1.3.6.1.4.1.56387.1.1
+
+const ED25519_KEY_LENGTH: usize = 32;
+// Uncompressed SEC1 encoding: 0x04 followed by the x- and y-coordinate.
+const EC_UNCOMPRESSED_KEY_LENGTH: usize = 65;
+const EC_COORDINATE_LENGTH: usize = 32;
+const RSA_MIN_MODULUS_LENGTH: usize = 256;
+
+// COSE labels and values, cf. https://www.iana.org/assignments/cose/cose.xhtml
+const COSE_LABEL_CRV: i64 = -1;
+const COSE_LABEL_X: i64 = -2;
+const COSE_LABEL_Y: i64 = -3;
+const COSE_LABEL_RSA_N: i64 = -1;
+const COSE_LABEL_RSA_E: i64 = -2;
+const COSE_KTY_OKP: i64 = 1;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_KTY_RSA: i64 = 3;
+const COSE_CRV_P256: i64 = 1;
+
+/// The algorithm of a COSE key accepted by the IC for WebAuthn.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum CoseAlgorithm {
+    /// ECDSA on curve P-256 with SHA-256.
+    Es256,
+    /// RSASSA-PKCS1-v1_5 with SHA-256.
+    Rs256,
+}
+
+impl CoseAlgorithm {
+    const ES256: i64 = -7;
+    const RS256: i64 = -257;
+
+    /// Returns the algorithm identifier registered with IANA.
+    pub fn id(&self) -> i64 {
+        match self {
+            CoseAlgorithm::Es256 => Self::ES256,
+            CoseAlgorithm::Rs256 => Self::RS256,
+        }
+    }
+}
+
+/// The algorithm of a session public key.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum SessionKeyAlgorithm {
+    Ed25519,
+    EcdsaP256,
+    EcdsaSecp256k1,
+    /// A DER-wrapped COSE key, as used by WebAuthn.
+    WebAuthn(CoseAlgorithm),
+    CanisterSig,
+}
+
+impl fmt::Display for SessionKeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionKeyAlgorithm::Ed25519 => f.write_str("Ed25519"),
+            SessionKeyAlgorithm::EcdsaP256 => f.write_str("ECDSA P-256"),
+            SessionKeyAlgorithm::EcdsaSecp256k1 => f.write_str("ECDSA secp256k1"),
+            SessionKeyAlgorithm::WebAuthn(alg) => write!(f, "WebAuthn ({:?})", alg),
+            SessionKeyAlgorithm::CanisterSig => f.write_str("canister signature"),
+        }
+    }
+}
+
+/// A validated DER-encoded session public key.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SessionPublicKey {
+    algorithm: SessionKeyAlgorithm,
+    der: Vec<u8>,
+}
+
+impl SessionPublicKey {
+    /// Parses the given DER-encoded public key, failing if it is not a valid key
+    /// of one of the algorithms supported by the IC.
+    pub fn from_der(pk_der: &[u8]) -> Result<Self, String> {
+        let algorithm = parse_algorithm(pk_der)?;
+        Ok(SessionPublicKey {
+            algorithm,
+            der: pk_der.to_vec(),
+        })
+    }
+
+    /// Returns the algorithm of this key.
+    pub fn algorithm(&self) -> SessionKeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the DER-encoding of this key.
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Returns the DER-encoding of this key.
+    pub fn into_der(self) -> Vec<u8> {
+        self.der
+    }
+}
+
+impl TryFrom<&[u8]> for SessionPublicKey {
+    type Error = String;
+
+    fn try_from(pk_der: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_der(pk_der)
+    }
+}
+
+fn parse_algorithm(pk_der: &[u8]) -> Result<SessionKeyAlgorithm, String> {
+    let spki = read_only_tlv(pk_der, DER_TAG_SEQUENCE)
+        .map_err(|e| format!("invalid session pk DER: {}", e))?;
+    let (alg_id, rest) = read_tlv(spki, DER_TAG_SEQUENCE)
+        .map_err(|e| format!("invalid algorithm identifier in session pk: {}", e))?;
+    let bit_string = read_only_tlv(rest, DER_TAG_BIT_STRING)
+        .map_err(|e| format!("invalid key in session pk: {}", e))?;
+    let key = match bit_string.split_first() {
+        Some((0, key)) => key,
+        _ => return Err("invalid bit string in session pk".to_string()),
+    };
+    let (oid, params) = read_tlv(alg_id, DER_TAG_OID)
+        .map_err(|e| format!("invalid algorithm identifier in session pk: {}", e))?;
+
+    match oid {
+        OID_ED25519 => {
+            expect_no_params(params)?;
+            check_length(key, ED25519_KEY_LENGTH, "Ed25519")?;
+            Ok(SessionKeyAlgorithm::Ed25519)
+        }
+        OID_EC_PUBLIC_KEY => {
+            let curve = read_only_tlv(params, DER_TAG_OID)
+                .map_err(|e| format!("invalid curve in session pk: {}", e))?;
+            let algorithm = match curve {
+                OID_P256 => SessionKeyAlgorithm::EcdsaP256,
+                OID_SECP256K1 => SessionKeyAlgorithm::EcdsaSecp256k1,
+                _ => return Err("unsupported curve of ECDSA session pk".to_string()),
+            };
+            check_length(key, EC_UNCOMPRESSED_KEY_LENGTH, "ECDSA")?;
+            if key[0] != 0x04 {
+                return Err("ECDSA session pk is not uncompressed".to_string());
+            }
+            Ok(algorithm)
+        }
+        OID_IC_COSE => {
+            expect_no_params(params)?;
+            parse_cose_algorithm(key).map(SessionKeyAlgorithm::WebAuthn)
+        }
+        // The algorithm identifier of canister signature public keys has no parameters.
+        _ if alg_id == &CANISTER_SIG_PK_DER_OID[2..] => {
+            CanisterSigPublicKey::try_from(pk_der)?;
+            Ok(SessionKeyAlgorithm::CanisterSig)
+        }
+        _ => Err(format!(
+            "unsupported algorithm of session pk (OID 0x{})",
+            hex::encode(oid)
+        )),
+    }
+}
+
+fn parse_cose_algorithm(pk_cose: &[u8]) -> Result<CoseAlgorithm, String> {
+    let cbor::Value::Map(cose_key) = cbor::from_slice::<cbor::Value>(pk_cose)
+        .map_err(|e| format!("failed to parse COSE session pk: {}", e))?
+    else {
+        return Err("COSE session pk is not a CBOR map".to_string());
+    };
+    let get = |label: i64| {
+        cose_key
+            .iter()
+            .find(|(key, _)| *key == cbor::Value::from(label))
+            .map(|(_, value)| value)
+    };
+    let get_int = |label: i64| {
+        get(label)
+            .and_then(cbor::Value::as_integer)
+            .and_then(|i| i64::try_from(i).ok())
+    };
+    let get_bytes = |label: i64| get(label).and_then(cbor::Value::as_bytes);
+    let alg = get_int(COSE_LABEL_ALG);
+    let has_alg = |expected: i64| alg.is_none() || alg == Some(expected);
+
+    match get_int(COSE_LABEL_KTY) {
+        Some(COSE_KTY_EC2) => {
+            if !has_alg(CoseAlgorithm::ES256) || get_int(COSE_LABEL_CRV) != Some(COSE_CRV_P256) {
+                return Err("unsupported algorithm of COSE EC2 session pk".to_string());
+            }
+            for label in [COSE_LABEL_X, COSE_LABEL_Y] {
+                match get_bytes(label) {
+                    Some(c) if c.len() == EC_COORDINATE_LENGTH => {}
+                    _ => return Err("invalid coordinate in COSE EC2 session pk".to_string()),
+                }
+            }
+            Ok(CoseAlgorithm::Es256)
+        }
+        // The IC does not accept EdDSA (or any other OKP) keys for WebAuthn.
+        Some(COSE_KTY_OKP) => {
+            Err("unsupported WebAuthn algorithm of COSE OKP session pk".to_string())
+        }
+        Some(COSE_KTY_RSA) => {
+            if !has_alg(CoseAlgorithm::RS256) {
+                return Err("unsupported algorithm of COSE RSA session pk".to_string());
+            }
+            match (get_bytes(COSE_LABEL_RSA_N), get_bytes(COSE_LABEL_RSA_E)) {
+                (Some(n), Some(e)) if n.len() >= RSA_MIN_MODULUS_LENGTH && !e.is_empty() => {
+                    Ok(CoseAlgorithm::Rs256)
+                }
+                _ => Err("invalid key in COSE RSA session pk".to_string()),
+            }
+        }
+        _ => Err("unsupported key type of COSE session pk".to_string()),
+    }
+}
+
+fn expect_no_params(params: &[u8]) -> Result<(), String> {
+    if !params.is_empty() {
+        return Err("unexpected algorithm parameters in session pk".to_string());
+    }
+    Ok(())
+}
+
+fn check_length(key: &[u8], expected: usize, algorithm: &str) -> Result<(), String> {
+    if key.len() != expected {
+        return Err(format!(
+            "invalid {} session pk length: {} bytes, expected {}",
+            algorithm,
+            key.len(),
+            expected
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a DER element with the given tag, returning its contents and the remaining bytes.
+/// Only lengths of up to two bytes are supported, which is sufficient for public keys.
+fn read_tlv(der: &[u8], tag: u8) -> Result<(&[u8], &[u8]), String> {
+    let [actual_tag, first_len, rest @ ..] = der else {
+        return Err("truncated DER element".to_string());
+    };
+    if *actual_tag != tag {
+        return Err(format!(
+            "expected DER tag 0x{:02x}, found 0x{:02x}",
+            tag, actual_tag
+        ));
+    }
+    let (len, rest) = match *first_len {
+        len @ 0..=0x7f => (usize::from(len), rest),
+        0x81 => match rest {
+            [len @ 0x80..=0xff, rest @ ..] => (usize::from(*len), rest),
+            _ => return Err("invalid DER length".to_string()),
+        },
+        0x82 => match rest {
+            [hi @ 0x01..=0xff, lo, rest @ ..] => (usize::from(*hi) << 8 | usize::from(*lo), rest),
+            _ => return Err("invalid DER length".to_string()),
+        },
+        _ => return Err("unsupported DER length".to_string()),
+    };
+    if rest.len() < len {
+        return Err("truncated DER element".to_string());
+    }
+    Ok(rest.split_at(len))
+}
+
+/// Like [read_tlv], but fails if there are bytes after the element.
+fn read_only_tlv(der: &[u8], tag: u8) -> Result<&[u8], String> {
+    let (contents, rest) = read_tlv(der, tag)?;
+    if !rest.is_empty() {
+        return Err(format!("{} trailing bytes after DER element", rest.len()));
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use candid::Principal;
+
+    const ED25519_PK_DER: &str =
+        "302a300506032b6570032100f2a8dc6a5b0bbe2b07dd8c0ebe6c3f4b4c1bf7cc2bc86b4e0d3e0a70b7a13e65";
+    const P256_PK_DER: &str = "3059301306072a8648ce3d020106082a8648ce3d030107034200046b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c2964fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5";
+    const SECP256K1_PK_DER: &str = "3056301006072a8648ce3d020106052b8104000a0342000479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+
+    fn der_tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut der = vec![tag];
+        match contents.len() {
+            len @ 0..=0x7f => der.push(len as u8),
+            len @ 0x80..=0xff => der.extend([0x81, len as u8]),
+            len => der.extend([&[0x82], &(len as u16).to_be_bytes()[..]].concat()),
+        }
+        der.extend(contents);
+        der
+    }
+
+    fn spki(oid: &[u8], key: &[u8]) -> Vec<u8> {
+        let alg_id = der_tlv(DER_TAG_SEQUENCE, &der_tlv(DER_TAG_OID, oid));
+        let bit_string = der_tlv(DER_TAG_BIT_STRING, &[&[0], key].concat());
+        der_tlv(DER_TAG_SEQUENCE, &[alg_id, bit_string].concat())
+    }
+
+    fn cose_pk_der(pk_cose: &[u8]) -> Vec<u8> {
+        spki(OID_IC_COSE, pk_cose)
+    }
+
+    fn cose_key(entries: Vec<(i64, cbor::Value)>) -> Vec<u8> {
+        let map = entries
+            .into_iter()
+            .map(|(label, value)| (cbor::Value::from(label), value))
+            .collect();
+        cbor::to_vec(&cbor::Value::Map(map)).unwrap()
+    }
+
+    fn es256_cose_key() -> Vec<(i64, cbor::Value)> {
+        vec![
+            (COSE_LABEL_KTY, COSE_KTY_EC2.into()),
+            (COSE_LABEL_ALG, CoseAlgorithm::ES256.into()),
+            (COSE_LABEL_CRV, COSE_CRV_P256.into()),
+            (COSE_LABEL_X, cbor::Value::Bytes(vec![1; 32])),
+            (COSE_LABEL_Y, cbor::Value::Bytes(vec![2; 32])),
+        ]
+    }
+
+    fn algorithm(pk_der_hex: &str) -> Result<SessionKeyAlgorithm, String> {
+        SessionPublicKey::from_der(&hex::decode(pk_der_hex).unwrap()).map(|pk| pk.algorithm())
+    }
+
+    #[test]
+    fn should_detect_der_key_algorithms() {
+        assert_eq!(algorithm(ED25519_PK_DER), Ok(SessionKeyAlgorithm::Ed25519));
+        assert_eq!(algorithm(P256_PK_DER), Ok(SessionKeyAlgorithm::EcdsaP256));
+        assert_eq!(
+            algorithm(SECP256K1_PK_DER),
+            Ok(SessionKeyAlgorithm::EcdsaSecp256k1)
+        );
+    }
+
+    #[test]
+    fn should_detect_canister_sig_key() {
+        let pk = CanisterSigPublicKey::new(
+            Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]),
+            vec![42; 32],
+        );
+        let session_pk = SessionPublicKey::from_der(&pk.to_der()).unwrap();
+        assert_eq!(session_pk.algorithm(), SessionKeyAlgorithm::CanisterSig);
+        assert_eq!(session_pk.as_der(), pk.to_der());
+    }
+
+    #[test]
+    fn should_reject_eddsa_webauthn_key() {
+        const COSE_ALG_EDDSA: i64 = -8;
+        const COSE_CRV_ED25519: i64 = 6;
+        let eddsa = cose_pk_der(&cose_key(vec![
+            (COSE_LABEL_KTY, COSE_KTY_OKP.into()),
+            (COSE_LABEL_ALG, COSE_ALG_EDDSA.into()),
+            (COSE_LABEL_CRV, COSE_CRV_ED25519.into()),
+            (COSE_LABEL_X, cbor::Value::Bytes(vec![1; 32])),
+        ]));
+        assert_matches!(
+            SessionPublicKey::from_der(&eddsa),
+            Err(e) if e.contains("unsupported WebAuthn algorithm")
+        );
+    }
+
+    #[test]
+    fn should_detect_webauthn_key_algorithms() {
+        let es256 = cose_pk_der(&cose_key(es256_cose_key()));
+        assert_matches!(
+            SessionPublicKey::from_der(&es256).map(|pk| pk.algorithm()),
+            Ok(SessionKeyAlgorithm::WebAuthn(CoseAlgorithm::Es256))
+        );
+
+        // The modulus makes the key longer than 255 bytes, hence it has a two-byte DER length.
+        let rsa = cose_pk_der(&cose_key(vec![
+            (COSE_LABEL_KTY, COSE_KTY_RSA.into()),
+            (COSE_LABEL_ALG, CoseAlgorithm::RS256.into()),
+            (COSE_LABEL_RSA_N, cbor::Value::Bytes(vec![0xff; 256])),
+            (COSE_LABEL_RSA_E, cbor::Value::Bytes(vec![1, 0, 1])),
+        ]));
+        assert_matches!(
+            SessionPublicKey::from_der(&rsa).map(|pk| pk.algorithm()),
+            Ok(SessionKeyAlgorithm::WebAuthn(CoseAlgorithm::Rs256))
+        );
+    }
+
+    #[test]
+    fn should_reject_garbage() {
+        assert_matches!(SessionPublicKey::from_der(&[]), Err(_));
+        assert_matches!(SessionPublicKey::from_der(&[1, 2, 3]), Err(e) if e.contains("DER"));
+        assert_matches!(
+            SessionPublicKey::try_from(&hex::decode(ED25519_PK_DER).unwrap()[..43]),
+            Err(e) if e.contains("truncated")
+        );
+        let mut trailing = hex::decode(ED25519_PK_DER).unwrap();
+        trailing.push(0);
+        assert_matches!(
+            SessionPublicKey::from_der(&trailing),
+            Err(e) if e.contains("trailing bytes")
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_keys() {
+        // Ed25519 key with 31 bytes
+        assert_matches!(
+            algorithm("3029300506032b6570032000f2a8dc6a5b0bbe2b07dd8c0ebe6c3f4b4c1bf7cc2bc86b4e0d3e0a70b7a13e"),
+            Err(e) if e.contains("invalid Ed25519 session pk length: 31 bytes")
+        );
+        // compressed P-256 key
+        assert_matches!(
+            algorithm("3039301306072a8648ce3d020106082a8648ce3d030107032200036b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+            Err(e) if e.contains("invalid ECDSA session pk length")
+        );
+        // P-384 curve
+        let alg_id = der_tlv(
+            DER_TAG_SEQUENCE,
+            &[
+                der_tlv(DER_TAG_OID, OID_EC_PUBLIC_KEY),
+                der_tlv(DER_TAG_OID, b"\x2b\x81\x04\x00\x22"),
+            ]
+            .concat(),
+        );
+        let bit_string = der_tlv(DER_TAG_BIT_STRING, &[&[0, 4][..], &[1; 96]].concat());
+        let p384 = der_tlv(DER_TAG_SEQUENCE, &[alg_id, bit_string].concat());
+        assert_matches!(
+            SessionPublicKey::from_der(&p384),
+            Err(e) if e.contains("unsupported curve")
+        );
+        // RSA SPKI
+        assert_matches!(
+            algorithm("3011300b06092a864886f70d01010103020000"),
+            Err(e) if e.contains("unsupported algorithm of session pk")
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_cose_keys() {
+        let mut wrong_curve = es256_cose_key();
+        wrong_curve[2].1 = 2.into();
+        assert_matches!(
+            SessionPublicKey::from_der(&cose_pk_der(&cose_key(wrong_curve))),
+            Err(e) if e.contains("unsupported algorithm of COSE EC2")
+        );
+
+        let mut short_coordinate = es256_cose_key();
+        short_coordinate[4].1 = cbor::Value::Bytes(vec![2; 31]);
+        assert_matches!(
+            SessionPublicKey::from_der(&cose_pk_der(&cose_key(short_coordinate))),
+            Err(e) if e.contains("invalid coordinate")
+        );
+
+        assert_matches!(
+            SessionPublicKey::from_der(&cose_pk_der(&[0xa0])),
+            Err(e) if e.contains("unsupported key type")
+        );
+        assert_matches!(
+            SessionPublicKey::from_der(&cose_pk_der(&[0x01])),
+            Err(e) if e.contains("not a CBOR map")
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_canister_sig_key() {
+        // The canister id is announced to be 10 bytes long, but only one byte follows.
+        let pk_der = spki(&CANISTER_SIG_PK_DER_OID[4..], &[10, 1]);
+        assert_matches!(SessionPublicKey::from_der(&pk_der), Err(_));
+    }
+}
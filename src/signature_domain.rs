@@ -0,0 +1,131 @@
+use std::fmt;
+
+/// A domain separator for signatures, see [crate::hash_with_domain].
+///
+/// The domain is prefixed with its length as a single byte when hashing, so it must be
+/// at most [SignatureDomain::MAX_LENGTH] bytes long, which is checked at construction.
+/// The domains used by the IC are available as constants, e.g.
+/// [SignatureDomain::IC_REQUEST_AUTH_DELEGATION]. Applications declare their own domains
+/// with [SignatureDomain::from_static] or [SignatureDomain::new]:
+/// ```
+/// use ic_canister_sig_creation::SignatureDomain;
+///
+/// const MY_DOMAIN: SignatureDomain = SignatureDomain::from_static(b"my-app-claims");
+/// ```
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SignatureDomain<'a>(&'a [u8]);
+
+impl SignatureDomain<'static> {
+    /// Domain for signatures on IC requests (i.e. on request ids).
+    pub const IC_REQUEST: Self = Self::from_static(b"ic-request");
+    /// Domain for signatures on IC request auth delegations,
+    /// see [crate::delegation_signature_msg].
+    pub const IC_REQUEST_AUTH_DELEGATION: Self = Self::from_static(crate::DELEGATION_SIG_DOMAIN);
+    /// Domain for the signature on the state tree root hash in certificates.
+    pub const IC_STATE_ROOT: Self = Self::from_static(b"ic-state-root");
+    /// Domain for node signatures on query responses.
+    pub const IC_RESPONSE: Self = Self::from_static(b"ic-response");
+
+    /// Constructs a domain from a static byte string.
+    ///
+    /// Panics if the domain is longer than [SignatureDomain::MAX_LENGTH], which is reported
+    /// at compile time if used to initialize a constant.
+    pub const fn from_static(domain: &'static [u8]) -> Self {
+        assert!(
+            domain.len() <= Self::MAX_LENGTH,
+            "signature domain longer than 255 bytes"
+        );
+        SignatureDomain(domain)
+    }
+}
+
+impl<'a> SignatureDomain<'a> {
+    /// The maximum length of a domain, in bytes.
+    pub const MAX_LENGTH: usize = u8::MAX as usize;
+
+    /// Constructs a domain, checking that it is at most [SignatureDomain::MAX_LENGTH] bytes long.
+    pub fn new(domain: &'a [u8]) -> Result<Self, String> {
+        if domain.len() > Self::MAX_LENGTH {
+            return Err(format!(
+                "signature domain too long: {} bytes, at most {} allowed",
+                domain.len(),
+                Self::MAX_LENGTH
+            ));
+        }
+        Ok(SignatureDomain(domain))
+    }
+
+    /// Returns the bytes of the domain, without the length prefix.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for SignatureDomain<'a> {
+    type Error = String;
+
+    fn try_from(domain: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::new(domain)
+    }
+}
+
+impl fmt::Debug for SignatureDomain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(domain) => write!(f, "SignatureDomain({:?})", domain),
+            Err(_) => write!(f, "SignatureDomain(0x{})", hex::encode(self.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn should_accept_domain_of_max_length() {
+        let domain = [b'a'; SignatureDomain::MAX_LENGTH];
+        assert_matches!(SignatureDomain::new(&domain), Ok(d) if d.as_bytes() == domain);
+    }
+
+    #[test]
+    fn should_reject_too_long_domain() {
+        let domain = [b'a'; SignatureDomain::MAX_LENGTH + 1];
+        assert_matches!(
+            SignatureDomain::new(&domain),
+            Err(e) if e.contains("too long: 256 bytes")
+        );
+        assert_matches!(SignatureDomain::try_from(&domain[..]), Err(_));
+    }
+
+    #[test]
+    #[should_panic(expected = "longer than 255 bytes")]
+    fn should_panic_on_too_long_static_domain() {
+        static DOMAIN: [u8; 256] = [b'a'; 256];
+        SignatureDomain::from_static(&DOMAIN);
+    }
+
+    #[test]
+    fn should_provide_ic_domains() {
+        assert_eq!(SignatureDomain::IC_REQUEST.as_bytes(), b"ic-request");
+        assert_eq!(
+            SignatureDomain::IC_REQUEST_AUTH_DELEGATION.as_bytes(),
+            b"ic-request-auth-delegation"
+        );
+        assert_eq!(SignatureDomain::IC_STATE_ROOT.as_bytes(), b"ic-state-root");
+        assert_eq!(SignatureDomain::IC_RESPONSE.as_bytes(), b"ic-response");
+    }
+
+    #[test]
+    fn should_debug_format_domain() {
+        assert_eq!(
+            format!("{:?}", SignatureDomain::IC_REQUEST),
+            "SignatureDomain(\"ic-request\")"
+        );
+        assert_eq!(
+            format!("{:?}", SignatureDomain::new(&[0xff, 0x00]).unwrap()),
+            "SignatureDomain(0xff00)"
+        );
+    }
+}
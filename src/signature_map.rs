@@ -1,13 +1,14 @@
 //! Maintains signatures with associated expirations.
-use crate::{hash_bytes, hash_with_domain, CanisterSig};
+use crate::{hash_bytes, hash_with_domain, CanisterSig, SignatureDomain};
+use candid::CandidType;
 use ic_cdk::api::{data_certificate, time};
-use ic_certification::{
-    fork, labeled, leaf, leaf_hash, pruned, AsHashTree, Hash, HashTree, RbTree,
-};
-use serde::Serialize;
-use serde_bytes::ByteBuf;
+use ic_certification::{fork, labeled, leaf, pruned, AsHashTree, Hash, HashTree, RbTree};
+use serde::Deserialize;
 use std::borrow::Cow;
-use std::collections::BinaryHeap;
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::mem::size_of;
 use thiserror::Error;
 
 const MINUTE_NS: u64 = 60 * 1_000_000_000;
@@ -15,33 +16,48 @@ const MINUTE_NS: u64 = 60 * 1_000_000_000;
 #[allow(clippy::identity_op)]
 const SIGNATURE_EXPIRATION_PERIOD_NS: u64 = 1 * MINUTE_NS;
 const MAX_SIGS_TO_PRUNE: usize = 50;
+// Rough per-node overhead of an `RbTree` (cached subtree hash, child pointers and color),
+// used for estimating the heap usage of the map.
+const RB_TREE_NODE_OVERHEAD_BYTES: usize = size_of::<Hash>() + 2 * size_of::<usize>() + 8;
+// Rough per-entry overhead of a `BTreeSet` (half-full nodes of up to 11 entries with child
+// pointers), used for estimating the heap usage of the map.
+const BTREE_ENTRY_OVERHEAD_BYTES: usize = 2 * size_of::<usize>();
+// The hash of an empty leaf, i.e. `leaf_hash(b"")`, which is the value of every signature in
+// the certified map. It is a constant, so that updating the cached subtree hashes of the
+// certified map does not re-hash the leaves.
+const EMPTY_LEAF_HASH: Hash = [
+    0xd0, 0x01, 0xf3, 0xe7, 0xb8, 0x21, 0x66, 0xc6, 0xd3, 0x43, 0xa1, 0xef, 0xe7, 0x76, 0xe9, 0x6a,
+    0xc0, 0x2a, 0x23, 0xa5, 0x1e, 0x08, 0x98, 0xbc, 0x2c, 0x4e, 0x32, 0x3f, 0xce, 0x0e, 0x62, 0x2c,
+];
 pub const LABEL_SIG: &[u8] = b"sig";
+
+/// The value of a signature leaf in the certified map. It is certified as an empty leaf,
+/// the other fields are only kept for bookkeeping.
 #[derive(Default)]
-struct Unit;
+struct SigEntry {
+    // A `Cell`, so that the expiration can be extended without `RbTree::modify`, which would
+    // re-hash the path to the leaf even though the certified value does not change.
+    expires_at: Cell<u64>,
+    // Hash of the signature domain, to support revoking all signatures of a domain.
+    domain_hash: Hash,
+}
 
-impl AsHashTree for Unit {
+impl AsHashTree for SigEntry {
     fn root_hash(&self) -> Hash {
-        leaf_hash(&b""[..])
+        EMPTY_LEAF_HASH
     }
     fn as_hash_tree(&self) -> HashTree {
         leaf(Cow::from(&b""[..]))
     }
 }
 
-#[derive(PartialEq, Eq)]
-struct SigExpiration {
-    expires_at: u64,
-    seed_hash: Hash,
-    msg_hash: Hash,
-}
-
 /// Inputs to create and retrieve a canister signature.
 /// - domain: The domain is used to ensure that the same signature cannot be misused in a different context.
 /// - seed: The seed is used to derive the canister signature public key to use for this particular signature.
 /// - message: The message to sign.
 #[derive(PartialEq, Eq)]
 pub struct CanisterSigInputs<'a> {
-    pub domain: &'a [u8],
+    pub domain: SignatureDomain<'a>,
     pub seed: &'a [u8],
     pub message: &'a [u8],
 }
@@ -52,62 +68,397 @@ impl CanisterSigInputs<'_> {
     }
 }
 
-impl Ord for SigExpiration {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // BinaryHeap is a max heap, but we want expired entries
-        // first, hence the inversed order.
-        other.expires_at.cmp(&self.expires_at)
-    }
+/// The result of successfully adding a signature to a [SignatureMap].
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum AddedSignature {
+    /// The signature was not in the map before.
+    New { expires_at: u64 },
+    /// The signature was already in the map, and its expiration was extended.
+    Refreshed { expires_at: u64 },
 }
 
-impl PartialOrd for SigExpiration {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl AddedSignature {
+    /// Returns the effective expiration (in nanoseconds since the UNIX epoch) of the signature.
+    pub fn expires_at(&self) -> u64 {
+        match self {
+            AddedSignature::New { expires_at } | AddedSignature::Refreshed { expires_at } => {
+                *expires_at
+            }
+        }
     }
 }
 
+/// A map of canister signatures, certified as a tree of seed hashes to trees of message hashes
+/// (cf. [LABEL_SIG]), with an expiration for every signature.
+///
+/// The expirations are not part of the certified tree, but are indexed separately by expiration,
+/// so that expired signatures are pruned in order, and removing a signature from the index does
+/// not scan the map. Refreshing the expiration of a signature does not change the certified tree.
+/// Adding or removing a signature updates the nested [RbTree]s, which re-hash the path to it.
 #[derive(Default)]
 pub struct SignatureMap {
-    certified_map: RbTree<Hash, RbTree<Hash, Unit>>,
-    expiration_queue: BinaryHeap<SigExpiration>,
+    certified_map: RbTree<Hash, RbTree<Hash, SigEntry>>,
+    // Index of the signatures by expiration, seed hash and message hash, which is kept in sync
+    // with the expirations of the entries in `certified_map`.
+    expirations: BTreeSet<(u64, Hash, Hash)>,
+    limits: SignatureMapLimits,
+    strict_expiry: bool,
+    num_signatures: usize,
+    total_added: u64,
+    total_pruned: u64,
+    total_evicted: u64,
+    // The pending background pruning timer and the time it fires at, see [timers::schedule_pruning].
+    #[cfg(feature = "timers")]
+    pruning_timer: Option<(ic_cdk_timers::TimerId, u64)>,
+}
+
+/// Limits on the number of signatures kept in a [SignatureMap], see [SignatureMap::with_limits].
+/// By default, the map is unbounded.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SignatureMapLimits {
+    /// Maximum number of signatures in the map.
+    pub max_signatures: Option<usize>,
+    /// Maximum number of signatures per seed, i.e. per canister signature public key.
+    pub max_signatures_per_seed: Option<usize>,
+    /// What to do when adding a signature would exceed one of the limits.
+    pub policy: LimitPolicy,
+}
+
+/// The behaviour of [SignatureMap::add_signature] when a [SignatureMapLimits] limit is hit.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum LimitPolicy {
+    /// Reject the new signature with an error.
+    #[default]
+    Reject,
+    /// Evict the signature that expires soonest (among all signatures if the global limit is hit,
+    /// or among the signatures of the same seed if the per-seed limit is hit) to make room.
+    EvictSoonestExpiring,
+}
+
+/// Statistics about the contents of a [SignatureMap], see [SignatureMap::stats].
+#[derive(Clone, Eq, PartialEq, Debug, CandidType, Deserialize)]
+pub struct SignatureMapStats {
+    /// Number of signatures currently in the certified tree.
+    pub num_signatures: u64,
+    /// Number of distinct seeds with at least one signature in the certified tree.
+    pub num_seeds: u64,
+    /// Earliest expiration (in nanoseconds since the UNIX epoch) of the signatures in the map.
+    pub oldest_expiration: Option<u64>,
+    /// Latest expiration (in nanoseconds since the UNIX epoch) of the signatures in the map.
+    pub newest_expiration: Option<u64>,
+    /// Number of signatures in the map that are expired but not yet pruned.
+    pub num_expired_pending: u64,
+    /// Estimated heap memory (in bytes) used by the map.
+    pub estimated_heap_bytes: u64,
+    /// Number of signatures added since the map was created.
+    pub total_added: u64,
+    /// Number of signatures pruned since the map was created.
+    pub total_pruned: u64,
+    /// Number of signatures evicted due to [SignatureMapLimits] since the map was created.
+    pub total_evicted: u64,
+}
+
+impl SignatureMapStats {
+    /// Returns the statistics in the Prometheus text exposition format, see
+    /// https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    ///
+    /// All metric names are prefixed with the given `prefix`, e.g. `"ii_signature_map"`.
+    /// Expirations are omitted if the map is empty.
+    pub fn to_prometheus_text(&self, prefix: &str) -> String {
+        let metrics = [
+            (
+                "signatures",
+                "gauge",
+                "Number of signatures in the certified tree.",
+                Some(self.num_signatures),
+            ),
+            (
+                "seeds",
+                "gauge",
+                "Number of distinct seeds with at least one signature.",
+                Some(self.num_seeds),
+            ),
+            (
+                "oldest_expiration_timestamp_ns",
+                "gauge",
+                "Earliest signature expiration in nanoseconds since the UNIX epoch.",
+                self.oldest_expiration,
+            ),
+            (
+                "newest_expiration_timestamp_ns",
+                "gauge",
+                "Latest signature expiration in nanoseconds since the UNIX epoch.",
+                self.newest_expiration,
+            ),
+            (
+                "expired_pending",
+                "gauge",
+                "Number of expired signatures not yet pruned.",
+                Some(self.num_expired_pending),
+            ),
+            (
+                "estimated_heap_bytes",
+                "gauge",
+                "Estimated heap memory used by the signature map.",
+                Some(self.estimated_heap_bytes),
+            ),
+            (
+                "added_total",
+                "counter",
+                "Number of signatures added.",
+                Some(self.total_added),
+            ),
+            (
+                "pruned_total",
+                "counter",
+                "Number of signatures pruned.",
+                Some(self.total_pruned),
+            ),
+            (
+                "evicted_total",
+                "counter",
+                "Number of signatures evicted due to capacity limits.",
+                Some(self.total_evicted),
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, metric_type, help, value) in metrics {
+            let Some(value) = value else {
+                continue;
+            };
+            // Writing to a `String` cannot fail.
+            let _ = writeln!(text, "# HELP {prefix}_{name} {help}");
+            let _ = writeln!(text, "# TYPE {prefix}_{name} {metric_type}");
+            let _ = writeln!(text, "{prefix}_{name} {value}");
+        }
+        text
+    }
 }
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum CanisterSigError {
     #[error("Data certificates (which are required to create canister signatures) are only available in query calls.")]
     NoCertificate,
     #[error("No signature found for the given inputs.")]
     NoSignature,
+    #[error("Failed to encode the canister signature: {0}")]
+    EncodingFailed(String),
+    #[error("The signature map is full (at most {max_signatures} signatures).")]
+    CapacityExceeded { max_signatures: usize },
+    #[error(
+        "Too many signatures for the given seed (at most {max_signatures_per_seed} signatures)."
+    )]
+    SeedQuotaExceeded { max_signatures_per_seed: usize },
+    #[error("The signature expired at {expires_at} (nanoseconds since the UNIX epoch).")]
+    Expired { expires_at: u64 },
 }
 
 impl SignatureMap {
-    fn put(&mut self, seed: &[u8], message_hash: Hash, signature_expires_at: u64) {
-        let seed_hash = hash_bytes(seed);
+    /// Constructs an empty signature map that enforces the given limits in [SignatureMap::add_signature].
+    pub fn with_limits(limits: SignatureMapLimits) -> Self {
+        SignatureMap {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    /// Enables or disables strict expiry enforcement (disabled by default).
+    ///
+    /// By default, a signature can be retrieved until it is pruned, which may be after its
+    /// expiration. With strict expiry enforcement, [SignatureMap::get_signature_as_cbor] fails with
+    /// [CanisterSigError::Expired] for signatures whose expiration has passed.
+    pub fn set_strict_expiry(&mut self, strict_expiry: bool) {
+        self.strict_expiry = strict_expiry;
+    }
+
+    /// Puts the given signature into the map. If the signature is already present,
+    /// its expiration is extended to `signature_expires_at` (but never shortened),
+    /// which leaves the certified tree unchanged.
+    fn put(
+        &mut self,
+        domain: SignatureDomain,
+        seed_hash: Hash,
+        message_hash: Hash,
+        signature_expires_at: u64,
+    ) -> AddedSignature {
+        if let Some(entry) = self.entry(&seed_hash, &message_hash) {
+            let previous_expires_at = entry.expires_at.get();
+            if signature_expires_at <= previous_expires_at {
+                return AddedSignature::Refreshed {
+                    expires_at: previous_expires_at,
+                };
+            }
+            entry.expires_at.set(signature_expires_at);
+            self.expirations
+                .remove(&(previous_expires_at, seed_hash, message_hash));
+            self.expirations
+                .insert((signature_expires_at, seed_hash, message_hash));
+            return AddedSignature::Refreshed {
+                expires_at: signature_expires_at,
+            };
+        }
+
+        let entry = SigEntry {
+            expires_at: Cell::new(signature_expires_at),
+            domain_hash: hash_bytes(domain.as_bytes()),
+        };
         if self.certified_map.get(&seed_hash[..]).is_none() {
             let mut submap = RbTree::new();
-            submap.insert(message_hash, Unit);
+            submap.insert(message_hash, entry);
             self.certified_map.insert(seed_hash, submap);
         } else {
             self.certified_map.modify(&seed_hash[..], |submap| {
-                submap.insert(message_hash, Unit);
+                submap.insert(message_hash, entry);
             });
         }
-        self.expiration_queue.push(SigExpiration {
-            seed_hash,
-            msg_hash: message_hash,
+        self.expirations
+            .insert((signature_expires_at, seed_hash, message_hash));
+        self.num_signatures += 1;
+        self.total_added += 1;
+        AddedSignature::New {
             expires_at: signature_expires_at,
-        });
+        }
+    }
+
+    /// Returns the expiration (in nanoseconds since the UNIX epoch) of the signature for the given
+    /// inputs, or `None` if there is no such signature in the map. Unlike retrieving the signature,
+    /// this does not require a data certificate and does not build a witness.
+    pub fn signature_expiration(&self, sig_inputs: &CanisterSigInputs) -> Option<u64> {
+        self.expiration(&hash_bytes(sig_inputs.seed), &sig_inputs.message_hash())
+    }
+
+    fn expiration(&self, seed_hash: &Hash, message_hash: &Hash) -> Option<u64> {
+        self.entry(seed_hash, message_hash)
+            .map(|entry| entry.expires_at.get())
     }
 
+    fn entry(&self, seed_hash: &Hash, message_hash: &Hash) -> Option<&SigEntry> {
+        self.certified_map
+            .get(&seed_hash[..])?
+            .get(&message_hash[..])
+    }
+
+    /// Removes the signature with the given seed hash and message hash from the map.
+    /// Afterwards, the `certified_data` must be updated to the new [SignatureMap::root_hash].
+    ///
+    /// See also [SignatureMap::revoke_signature], which takes the [CanisterSigInputs] instead.
     pub fn delete(&mut self, seed_hash: Hash, message_hash: Hash) {
-        let mut is_empty = false;
-        self.certified_map.modify(&seed_hash[..], |m| {
-            m.delete(&message_hash[..]);
-            is_empty = m.is_empty();
-        });
-        if is_empty {
+        let Some(expires_at) = self.expiration(&seed_hash, &message_hash) else {
+            return;
+        };
+        self.expirations
+            .remove(&(expires_at, seed_hash, message_hash));
+        // If this is the last signature of the seed, the whole submap is deleted right away,
+        // as modifying it first would re-hash the path to it once more.
+        let is_last = self
+            .certified_map
+            .get(&seed_hash[..])
+            .is_some_and(|submap| submap.iter().nth(1).is_none());
+        if is_last {
             self.certified_map.delete(&seed_hash[..]);
+        } else {
+            self.certified_map.modify(&seed_hash[..], |submap| {
+                submap.delete(&message_hash[..]);
+            });
+        }
+        self.num_signatures -= 1;
+    }
+
+    /// Revokes the signature for the given inputs, if present, and returns the new root hash
+    /// to be set as `certified_data`.
+    pub fn revoke_signature(&mut self, sig_inputs: &CanisterSigInputs) -> Hash {
+        self.delete(hash_bytes(sig_inputs.seed), sig_inputs.message_hash());
+        self.root_hash()
+    }
+
+    /// Revokes all signatures for the given seed, e.g. when logging out a user,
+    /// and returns the new root hash to be set as `certified_data`.
+    pub fn revoke_seed(&mut self, seed: &[u8]) -> Hash {
+        let seed_hash = hash_bytes(seed);
+        let revoked: Vec<Hash> = self
+            .certified_map
+            .get(&seed_hash[..])
+            .map(|submap| submap.iter().map(|(msg_hash, _)| *msg_hash).collect())
+            .unwrap_or_default();
+        for msg_hash in revoked {
+            self.delete(seed_hash, msg_hash);
+        }
+        self.root_hash()
+    }
+
+    /// Revokes all signatures in the given signature domain,
+    /// and returns the new root hash to be set as `certified_data`.
+    ///
+    /// Unlike the other operations on the map, this iterates over all signatures in the map.
+    pub fn revoke_domain(&mut self, domain: SignatureDomain) -> Hash {
+        let domain_hash = hash_bytes(domain.as_bytes());
+        let mut revoked = Vec::new();
+        for (seed_hash, submap) in self.certified_map.iter() {
+            for (msg_hash, entry) in submap.iter() {
+                if entry.domain_hash == domain_hash {
+                    revoked.push((*seed_hash, *msg_hash));
+                }
+            }
+        }
+        for (seed_hash, msg_hash) in revoked {
+            self.delete(seed_hash, msg_hash);
+        }
+        self.root_hash()
+    }
+
+    fn num_signatures_for_seed(&self, seed_hash: &Hash) -> usize {
+        self.certified_map
+            .get(&seed_hash[..])
+            .map_or(0, |submap| submap.iter().count())
+    }
+
+    /// Makes sure that a new signature for the given seed can be added without exceeding
+    /// the limits, evicting other signatures if the [LimitPolicy] allows it.
+    fn ensure_capacity(&mut self, seed_hash: &Hash) -> Result<(), CanisterSigError> {
+        if let Some(max_signatures_per_seed) = self.limits.max_signatures_per_seed {
+            while self.num_signatures_for_seed(seed_hash) >= max_signatures_per_seed {
+                if self.limits.policy == LimitPolicy::Reject
+                    || !self.evict_soonest_expiring(Some(seed_hash))
+                {
+                    return Err(CanisterSigError::SeedQuotaExceeded {
+                        max_signatures_per_seed,
+                    });
+                }
+            }
+        }
+        if let Some(max_signatures) = self.limits.max_signatures {
+            while self.num_signatures >= max_signatures {
+                if self.limits.policy == LimitPolicy::Reject || !self.evict_soonest_expiring(None) {
+                    return Err(CanisterSigError::CapacityExceeded { max_signatures });
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Removes the signature that expires soonest, either among all signatures or among the
+    /// signatures of the given seed. Returns `false` if there was no signature to remove.
+    fn evict_soonest_expiring(&mut self, seed_hash: Option<&Hash>) -> bool {
+        let soonest_expiring = match seed_hash {
+            None => self
+                .expirations
+                .first()
+                .map(|(_, seed_hash, msg_hash)| (*seed_hash, *msg_hash)),
+            Some(seed_hash) => self.certified_map.get(&seed_hash[..]).and_then(|submap| {
+                submap
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at.get())
+                    .map(|(msg_hash, _)| (*seed_hash, *msg_hash))
+            }),
+        };
+        let Some((seed_hash, msg_hash)) = soonest_expiring else {
+            return false;
+        };
+        self.delete(seed_hash, msg_hash);
+        self.total_evicted += 1;
+        true
     }
 
     /// Removes a batch of expired signatures from the signature map.
@@ -120,22 +471,23 @@ impl SignatureMap {
     /// with the new root hash. Therefore, this function is only called by [add_signature]
     /// which requires updating the `certified_data` as well. This avoids the risk
     /// of clients forgetting to update `certified_data` as it would be a bug even
-    /// without pruning.
+    /// without pruning (or by the background pruning of the `timers` feature, see
+    /// [timers::schedule_pruning], which updates the `certified_data` afterwards).
     fn prune_expired(&mut self, now: u64) -> usize {
         let mut num_pruned = 0;
 
-        for _step in 0..MAX_SIGS_TO_PRUNE {
-            if let Some(expiration) = self.expiration_queue.peek() {
-                if expiration.expires_at > now {
-                    return num_pruned;
-                }
-            }
-            if let Some(expiration) = self.expiration_queue.pop() {
-                self.delete(expiration.seed_hash, expiration.msg_hash);
+        while num_pruned < MAX_SIGS_TO_PRUNE {
+            let Some(&(expires_at, seed_hash, msg_hash)) = self.expirations.first() else {
+                break;
+            };
+            if expires_at > now {
+                break;
             }
+            self.delete(seed_hash, msg_hash);
             num_pruned += 1;
         }
 
+        self.total_pruned += num_pruned as u64;
         num_pruned
     }
 
@@ -156,15 +508,26 @@ impl SignatureMap {
             sig_inputs,
             certificate,
             maybe_certified_assets_root_hash,
+            time(),
         )
     }
 
-    fn get_signature_as_cbor_internal(
+    pub(crate) fn get_signature_as_cbor_internal(
         &self,
         sig_inputs: &CanisterSigInputs,
         certificate: Vec<u8>,
         maybe_certified_assets_root_hash: Option<Hash>,
+        now: u64,
     ) -> Result<Vec<u8>, CanisterSigError> {
+        if self.strict_expiry {
+            match self.signature_expiration(sig_inputs) {
+                None => return Err(CanisterSigError::NoSignature),
+                Some(expires_at) if expires_at <= now => {
+                    return Err(CanisterSigError::Expired { expires_at })
+                }
+                Some(_) => {}
+            }
+        }
         let witness = self
             .witness(sig_inputs.seed, sig_inputs.message_hash())
             .ok_or(CanisterSigError::NoSignature)?;
@@ -183,35 +546,128 @@ impl SignatureMap {
             None => sigs_tree,
         };
 
-        let sig = CanisterSig {
-            certificate: ByteBuf::from(certificate),
-            tree,
-        };
-
-        let mut cbor = serde_cbor::ser::Serializer::new(Vec::new());
-        cbor.self_describe().unwrap();
-        sig.serialize(&mut cbor).unwrap();
-        Ok(cbor.into_inner())
+        CanisterSig::new(certificate, tree)
+            .to_cbor()
+            .map_err(CanisterSigError::EncodingFailed)
     }
 
     /// Adds a signature to the map, given the signature inputs.
-    pub fn add_signature(&mut self, sig_inputs: &CanisterSigInputs) {
+    ///
+    /// If the signature is already in the map, its expiration is extended instead, so that
+    /// re-preparing a signature never makes it expire earlier. The returned [AddedSignature]
+    /// tells which case applies, together with the effective expiration.
+    ///
+    /// Fails if adding the signature would exceed the [SignatureMapLimits] of this map
+    /// and the [LimitPolicy] is [LimitPolicy::Reject].
+    pub fn add_signature(
+        &mut self,
+        sig_inputs: &CanisterSigInputs,
+    ) -> Result<AddedSignature, CanisterSigError> {
+        let now = time();
+        self.add_signature_internal(sig_inputs, now)
+    }
+
+    pub(crate) fn add_signature_internal(
+        &mut self,
+        sig_inputs: &CanisterSigInputs,
+        now: u64,
+    ) -> Result<AddedSignature, CanisterSigError> {
+        self.add_signature_until_internal(sig_inputs, u64::MAX, now)
+    }
+
+    /// Like [SignatureMap::add_signature], but the signature expires at `expires_at` at the latest,
+    /// e.g. so that the signature on a delegation does not stay retrievable after the delegation
+    /// expired.
+    ///
+    /// Fails with [CanisterSigError::Expired] if `expires_at` is not in the future.
+    pub fn add_signature_until(
+        &mut self,
+        sig_inputs: &CanisterSigInputs,
+        expires_at: u64,
+    ) -> Result<AddedSignature, CanisterSigError> {
         let now = time();
-        self.add_signature_internal(sig_inputs, now);
+        self.add_signature_until_internal(sig_inputs, expires_at, now)
     }
 
-    fn add_signature_internal(&mut self, sig_inputs: &CanisterSigInputs, now: u64) {
+    fn add_signature_until_internal(
+        &mut self,
+        sig_inputs: &CanisterSigInputs,
+        max_expires_at: u64,
+        now: u64,
+    ) -> Result<AddedSignature, CanisterSigError> {
+        if max_expires_at <= now {
+            return Err(CanisterSigError::Expired {
+                expires_at: max_expires_at,
+            });
+        }
         self.prune_expired(now);
-        let expires_at = now.saturating_add(SIGNATURE_EXPIRATION_PERIOD_NS);
-        self.put(sig_inputs.seed, sig_inputs.message_hash(), expires_at);
+        let seed_hash = hash_bytes(sig_inputs.seed);
+        let message_hash = sig_inputs.message_hash();
+        if self.expiration(&seed_hash, &message_hash).is_none() {
+            self.ensure_capacity(&seed_hash)?;
+        }
+        let expires_at = now
+            .saturating_add(SIGNATURE_EXPIRATION_PERIOD_NS)
+            .min(max_expires_at);
+        Ok(self.put(sig_inputs.domain, seed_hash, message_hash, expires_at))
     }
 
+    /// Returns the earliest time (in nanoseconds since the UNIX epoch) at which
+    /// a signature in the map expires, or `None` if the map is empty.
+    pub fn next_expiration(&self) -> Option<u64> {
+        self.expirations
+            .first()
+            .map(|(expires_at, _, _)| *expires_at)
+    }
+
+    /// Returns the number of signatures in the map.
     pub fn len(&self) -> usize {
-        self.expiration_queue.len()
+        self.num_signatures
     }
 
     pub fn is_empty(&self) -> bool {
-        self.expiration_queue.is_empty()
+        self.num_signatures == 0
+    }
+
+    /// Returns statistics about the contents of this map, e.g. to be exported as metrics.
+    ///
+    /// Computing the statistics iterates over the whole map, so it should not be done
+    /// as part of every update call.
+    pub fn stats(&self) -> SignatureMapStats {
+        self.stats_internal(time())
+    }
+
+    fn stats_internal(&self, now: u64) -> SignatureMapStats {
+        let num_seeds = self.certified_map.iter().count();
+        let num_signatures = self.num_signatures;
+        let oldest_expiration = self.next_expiration();
+        let newest_expiration = self
+            .expirations
+            .last()
+            .map(|(expires_at, _, _)| *expires_at);
+        let num_expired_pending = self
+            .expirations
+            .iter()
+            .take_while(|(expires_at, _, _)| *expires_at <= now)
+            .count() as u64;
+        let estimated_heap_bytes = (num_seeds + num_signatures)
+            * (size_of::<Hash>() + RB_TREE_NODE_OVERHEAD_BYTES)
+            + num_signatures
+                * (size_of::<SigEntry>()
+                    + size_of::<(u64, Hash, Hash)>()
+                    + BTREE_ENTRY_OVERHEAD_BYTES);
+
+        SignatureMapStats {
+            num_signatures: num_signatures as u64,
+            num_seeds: num_seeds as u64,
+            oldest_expiration,
+            newest_expiration,
+            num_expired_pending,
+            estimated_heap_bytes: estimated_heap_bytes as u64,
+            total_added: self.total_added,
+            total_pruned: self.total_pruned,
+            total_evicted: self.total_evicted,
+        }
     }
 
     pub fn root_hash(&self) -> Hash {
@@ -220,9 +676,8 @@ impl SignatureMap {
 
     pub fn witness(&self, seed: &[u8], message_hash: Hash) -> Option<HashTree> {
         let seed_hash = hash_bytes(seed);
-        self.certified_map
-            .get(&seed_hash[..])?
-            .get(&message_hash[..])?;
+        // Checking for the signature up front avoids building a costly proof of absence.
+        self.entry(&seed_hash, &message_hash)?;
         let witness = self
             .certified_map
             .nested_witness(&seed_hash[..], |nested| nested.witness(&message_hash[..]));
@@ -230,5 +685,11 @@ impl SignatureMap {
     }
 }
 
+#[cfg(feature = "timers")]
+pub mod timers;
+
+#[cfg(feature = "canbench-rs")]
+mod benches;
+
 #[cfg(test)]
 mod test;
@@ -0,0 +1,279 @@
+//! Benchmarks of the [SignatureMap] in Wasm instructions, run with
+//! [canbench](https://github.com/dfinity/canbench) (see `canbench.yml`).
+//!
+//! Every operation is benchmarked on maps of 1k, 10k and 100k signatures, both with a seed per
+//! signature (e.g. a delegation per user) and with 100 signatures per seed. The maps are set up
+//! outside of the measured code, so a benchmark only measures the operation itself.
+use super::*;
+use canbench_rs::{bench, bench_fn, BenchResult};
+use std::hint::black_box;
+
+const NOW: u64 = 1_697_000_000_000_000_000;
+const SIGNATURES_PER_SHARED_SEED: u64 = 100;
+// Roughly the size of a data certificate with a subnet delegation.
+const CERTIFICATE_SIZE: usize = 1_500;
+
+/// How the signatures of a benchmarked map are spread over the seeds.
+#[derive(Clone, Copy)]
+enum Seeds {
+    /// Every signature has its own seed.
+    Distinct,
+    /// [SIGNATURES_PER_SHARED_SEED] signatures per seed.
+    Shared,
+}
+
+impl Seeds {
+    /// Returns the seed of the `i`-th signature of a map of `num_signatures` signatures.
+    /// For `i >= num_signatures`, this is a new seed with distinct seeds, and an existing seed
+    /// with shared seeds.
+    fn seed(self, i: u64, num_signatures: u64) -> [u8; 8] {
+        let seed = match self {
+            Seeds::Distinct => i,
+            Seeds::Shared => i % (num_signatures / SIGNATURES_PER_SHARED_SEED),
+        };
+        seed.to_be_bytes()
+    }
+}
+
+fn message(i: u64) -> [u8; 8] {
+    i.to_le_bytes()
+}
+
+fn sig_inputs<'a>(seed: &'a [u8], message: &'a [u8]) -> CanisterSigInputs<'a> {
+    CanisterSigInputs {
+        domain: SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+        seed,
+        message,
+    }
+}
+
+/// Returns a map with `num_signatures` signatures added at [NOW].
+fn map_with_signatures(num_signatures: u64, seeds: Seeds) -> SignatureMap {
+    let mut map = SignatureMap::default();
+    for i in 0..num_signatures {
+        let seed = seeds.seed(i, num_signatures);
+        map.add_signature_internal(&sig_inputs(&seed, &message(i)), NOW)
+            .expect("failed to add signature");
+    }
+    map
+}
+
+fn bench_add_signature(num_signatures: u64, seeds: Seeds) -> BenchResult {
+    let mut map = map_with_signatures(num_signatures, seeds);
+    let seed = seeds.seed(num_signatures, num_signatures);
+    let message = message(num_signatures);
+    bench_fn(|| {
+        black_box(map.add_signature_internal(&sig_inputs(&seed, &message), NOW))
+            .expect("failed to add signature");
+    })
+}
+
+fn bench_get_signature_as_cbor(num_signatures: u64, seeds: Seeds) -> BenchResult {
+    let map = map_with_signatures(num_signatures, seeds);
+    let seed = seeds.seed(num_signatures / 2, num_signatures);
+    let message = message(num_signatures / 2);
+    bench_fn(|| {
+        black_box(map.get_signature_as_cbor_internal(
+            &sig_inputs(&seed, &message),
+            vec![0; CERTIFICATE_SIZE],
+            None,
+            NOW,
+        ))
+        .expect("failed to get signature");
+    })
+}
+
+/// Prunes a batch of (at most [MAX_SIGS_TO_PRUNE]) expired signatures.
+fn bench_prune_expired(num_signatures: u64, seeds: Seeds) -> BenchResult {
+    let mut map = map_with_signatures(num_signatures, seeds);
+    bench_fn(|| {
+        black_box(map.prune_expired(NOW + SIGNATURE_EXPIRATION_PERIOD_NS));
+    })
+}
+
+fn bench_witness(num_signatures: u64, seeds: Seeds) -> BenchResult {
+    let map = map_with_signatures(num_signatures, seeds);
+    let seed = seeds.seed(num_signatures / 2, num_signatures);
+    let message_hash = sig_inputs(&seed, &message(num_signatures / 2)).message_hash();
+    bench_fn(|| {
+        black_box(map.witness(&seed, message_hash)).expect("failed to get witness");
+    })
+}
+
+fn bench_root_hash(num_signatures: u64, seeds: Seeds) -> BenchResult {
+    let map = map_with_signatures(num_signatures, seeds);
+    bench_fn(|| {
+        black_box(map.root_hash());
+    })
+}
+
+#[bench(raw)]
+fn add_signature_1k() -> BenchResult {
+    bench_add_signature(1_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn add_signature_10k() -> BenchResult {
+    bench_add_signature(10_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn add_signature_100k() -> BenchResult {
+    bench_add_signature(100_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn add_signature_1k_shared_seeds() -> BenchResult {
+    bench_add_signature(1_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn add_signature_10k_shared_seeds() -> BenchResult {
+    bench_add_signature(10_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn add_signature_100k_shared_seeds() -> BenchResult {
+    bench_add_signature(100_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_1k() -> BenchResult {
+    bench_get_signature_as_cbor(1_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_10k() -> BenchResult {
+    bench_get_signature_as_cbor(10_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_100k() -> BenchResult {
+    bench_get_signature_as_cbor(100_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_1k_shared_seeds() -> BenchResult {
+    bench_get_signature_as_cbor(1_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_10k_shared_seeds() -> BenchResult {
+    bench_get_signature_as_cbor(10_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn get_signature_as_cbor_100k_shared_seeds() -> BenchResult {
+    bench_get_signature_as_cbor(100_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn prune_expired_1k() -> BenchResult {
+    bench_prune_expired(1_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn prune_expired_10k() -> BenchResult {
+    bench_prune_expired(10_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn prune_expired_100k() -> BenchResult {
+    bench_prune_expired(100_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn prune_expired_1k_shared_seeds() -> BenchResult {
+    bench_prune_expired(1_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn prune_expired_10k_shared_seeds() -> BenchResult {
+    bench_prune_expired(10_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn prune_expired_100k_shared_seeds() -> BenchResult {
+    bench_prune_expired(100_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn witness_1k() -> BenchResult {
+    bench_witness(1_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn witness_10k() -> BenchResult {
+    bench_witness(10_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn witness_100k() -> BenchResult {
+    bench_witness(100_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn witness_1k_shared_seeds() -> BenchResult {
+    bench_witness(1_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn witness_10k_shared_seeds() -> BenchResult {
+    bench_witness(10_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn witness_100k_shared_seeds() -> BenchResult {
+    bench_witness(100_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn root_hash_1k() -> BenchResult {
+    bench_root_hash(1_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn root_hash_10k() -> BenchResult {
+    bench_root_hash(10_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn root_hash_100k() -> BenchResult {
+    bench_root_hash(100_000, Seeds::Distinct)
+}
+
+#[bench(raw)]
+fn root_hash_1k_shared_seeds() -> BenchResult {
+    bench_root_hash(1_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn root_hash_10k_shared_seeds() -> BenchResult {
+    bench_root_hash(10_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn root_hash_100k_shared_seeds() -> BenchResult {
+    bench_root_hash(100_000, Seeds::Shared)
+}
+
+#[bench(raw)]
+fn refresh_signature_100k() -> BenchResult {
+    let mut map = map_with_signatures(100_000, Seeds::Distinct);
+    let seed = Seeds::Distinct.seed(50_000, 100_000);
+    let message = message(50_000);
+    bench_fn(|| {
+        black_box(map.add_signature_internal(&sig_inputs(&seed, &message), NOW + 1))
+            .expect("failed to refresh signature");
+    })
+}
+
+#[bench(raw)]
+fn revoke_signature_100k() -> BenchResult {
+    let mut map = map_with_signatures(100_000, Seeds::Distinct);
+    let seed = Seeds::Distinct.seed(50_000, 100_000);
+    let message = message(50_000);
+    bench_fn(|| {
+        black_box(map.revoke_signature(&sig_inputs(&seed, &message)));
+    })
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::parse_canister_sig_cbor;
 use assert_matches::assert_matches;
 use ic_certification::hash_tree::SubtreeLookupResult::Found;
 use ic_certification::{Hash, LookupResult};
@@ -10,6 +11,8 @@ fn hash_bytes(value: impl AsRef<[u8]>) -> Hash {
     hasher.finalize().into()
 }
 
+const DOMAIN: SignatureDomain = SignatureDomain::IC_REQUEST_AUTH_DELEGATION;
+
 fn seed(x: u64) -> Hash {
     hash_bytes(x.to_be_bytes())
 }
@@ -21,7 +24,7 @@ fn message(x: u64) -> Hash {
 #[test]
 fn test_signature_lookup() {
     let mut map = SignatureMap::default();
-    map.put(&seed(1), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
     assert_eq!(
         map.witness(&seed(1), message(1))
             .expect("failed to get a witness")
@@ -33,16 +36,17 @@ fn test_signature_lookup() {
 
     map.delete(hash_bytes(seed(1)), message(1));
     assert!(map.witness(&seed(1), message(1)).is_none());
+    assert!(map.is_empty());
 }
 
 #[test]
 fn test_signature_expiration() {
     let mut map = SignatureMap::default();
 
-    map.put(&seed(1), message(1), 10);
-    map.put(&seed(1), message(2), 20);
-    map.put(&seed(2), message(1), 15);
-    map.put(&seed(2), message(2), 25);
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(1)), message(2), 20);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(1), 15);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(2), 25);
 
     assert_eq!(2, map.prune_expired(/*time now*/ 19));
     assert!(map.witness(&seed(1), message(1)).is_none());
@@ -57,7 +61,7 @@ fn test_signature_expiration_limit() {
     let mut map = SignatureMap::default();
 
     for i in 0..100 {
-        map.put(&seed(i), message(i), 10 + i);
+        map.put(DOMAIN, hash_bytes(seed(i)), message(i), 10 + i);
     }
 
     assert_eq!(50, map.prune_expired(/*time now*/ 100));
@@ -70,29 +74,31 @@ fn test_signature_expiration_limit() {
     }
 }
 
+// `rand` is a dev-dependency on native targets only, see Cargo.toml.
+#[cfg(not(target_arch = "wasm32"))]
 #[test]
 fn test_random_modifications() {
     use rand::prelude::*;
 
     let mut map = SignatureMap::default();
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
     let window_size = 5;
 
     let mut pairs = Vec::new();
 
     for round in 1..100 {
-        let n_seeds = rng.gen_range(0..5);
+        let n_seeds = rng.random_range(0..5);
         for _i in 0..n_seeds {
             let mut seed = Hash::default();
             rng.fill_bytes(&mut seed);
 
-            let n_messages = rng.gen_range(0..5);
+            let n_messages = rng.random_range(0..5);
             for _k in 0..n_messages {
                 let mut message_hash = Hash::default();
                 rng.fill_bytes(&mut message_hash);
 
                 pairs.push((seed, message_hash));
-                map.put(seed.as_slice(), message_hash, round);
+                map.put(DOMAIN, hash_bytes(seed), message_hash, round);
             }
         }
 
@@ -115,20 +121,19 @@ fn test_signatures_pruned_on_add() {
     const TIME_NOW: u64 = 100;
     let mut map = SignatureMap::default();
 
-    let sig_inputs = CanisterSigInputs {
-        domain: b"ic-request-auth-delegation",
-        seed: &[1, 2, 3],
-        message: &[4, 5, 6],
-    };
-
     for i in 0..50 {
-        map.add_signature_internal(&sig_inputs, TIME_NOW + i);
+        map.add_signature_internal(&sig_inputs(&[1, 2, 3], &[i]), TIME_NOW + u64::from(i))
+            .expect("failed to add signature");
     }
 
     assert_eq!(map.len(), 50);
 
     // Pruning timeout is one minute
-    map.add_signature_internal(&sig_inputs, TIME_NOW + 2 * MINUTE_NS);
+    map.add_signature_internal(
+        &sig_inputs(&[1, 2, 3], &[4, 5, 6]),
+        TIME_NOW + 2 * MINUTE_NS,
+    )
+    .expect("failed to add signature");
     assert_eq!(map.len(), 1);
 }
 
@@ -137,19 +142,20 @@ fn test_signature_round_trip() {
     const TIME_NOW: u64 = 100;
     let certificate = vec![1u8, 2, 3];
     let sig_inputs = CanisterSigInputs {
-        domain: b"ic-request-auth-delegation",
+        domain: DOMAIN,
         seed: &[1, 2, 3],
         message: &[4, 5, 6],
     };
 
     let mut map = SignatureMap::default();
-    map.add_signature_internal(&sig_inputs, TIME_NOW);
+    map.add_signature_internal(&sig_inputs, TIME_NOW)
+        .expect("failed to add signature");
     let result = map
-        .get_signature_as_cbor_internal(&sig_inputs, certificate.clone(), None)
+        .get_signature_as_cbor_internal(&sig_inputs, certificate.clone(), None, TIME_NOW)
         .expect("failed to get signature");
 
     let sig: CanisterSig =
-        serde_cbor::from_slice(&result).expect("failed to deserialize signature");
+        parse_canister_sig_cbor(&result).expect("failed to deserialize signature");
     assert_eq!(sig.certificate.as_slice(), certificate.as_slice());
     let Found(subtree) = sig.tree.lookup_subtree([b"sig"]) else {
         panic!("expected to find a subtree");
@@ -169,12 +175,644 @@ fn test_signature_error_non_existing() {
     let map = SignatureMap::default();
 
     let sig_inputs = CanisterSigInputs {
-        domain: b"ic-request-auth-delegation",
+        domain: DOMAIN,
         seed: &[1, 2, 3],
         message: &[4, 5, 6],
     };
 
     let certificate = vec![1u8, 2, 3];
-    let result = map.get_signature_as_cbor_internal(&sig_inputs, certificate, None);
+    let result = map.get_signature_as_cbor_internal(&sig_inputs, certificate, None, 100);
+    assert_matches!(result, Err(CanisterSigError::NoSignature));
+}
+
+#[test]
+fn test_prune_expired_on_empty_map() {
+    let mut map = SignatureMap::default();
+    assert_eq!(0, map.prune_expired(/*time now*/ 100));
+}
+
+#[test]
+fn test_stats() {
+    let mut map = SignatureMap::default();
+    assert_eq!(
+        map.stats_internal(/*time now*/ 0),
+        SignatureMapStats {
+            num_signatures: 0,
+            num_seeds: 0,
+            oldest_expiration: None,
+            newest_expiration: None,
+            num_expired_pending: 0,
+            estimated_heap_bytes: 0,
+            total_added: 0,
+            total_pruned: 0,
+            total_evicted: 0,
+        }
+    );
+
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(1)), message(2), 20);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(1), 15);
+    map.put(DOMAIN, hash_bytes(seed(3)), message(1), 30);
+    assert_eq!(1, map.prune_expired(/*time now*/ 12));
+
+    let stats = map.stats_internal(/*time now*/ 25);
+    assert_eq!(stats.num_signatures, 3);
+    assert_eq!(stats.num_seeds, 3);
+    assert_eq!(stats.oldest_expiration, Some(15));
+    assert_eq!(stats.newest_expiration, Some(30));
+    assert_eq!(stats.num_expired_pending, 2);
+    assert!(stats.estimated_heap_bytes > 0);
+    assert_eq!(stats.total_added, 4);
+    assert_eq!(stats.total_pruned, 1);
+}
+
+#[test]
+fn test_stats_as_prometheus_text() {
+    let stats = SignatureMapStats {
+        num_signatures: 3,
+        num_seeds: 2,
+        oldest_expiration: None,
+        newest_expiration: Some(30),
+        num_expired_pending: 1,
+        estimated_heap_bytes: 1024,
+        total_added: 5,
+        total_pruned: 2,
+        total_evicted: 1,
+    };
+    let text = stats.to_prometheus_text("sigs");
+
+    assert!(text.contains(
+        "# HELP sigs_signatures Number of signatures in the certified tree.\n\
+         # TYPE sigs_signatures gauge\n\
+         sigs_signatures 3\n"
+    ));
+    assert!(text.contains("\nsigs_seeds 2\n"));
+    assert!(!text.contains("sigs_oldest_expiration_timestamp_ns"));
+    assert!(text.contains("\nsigs_newest_expiration_timestamp_ns 30\n"));
+    assert!(text.contains("\nsigs_expired_pending 1\n"));
+    assert!(text.contains("\nsigs_estimated_heap_bytes 1024\n"));
+    assert!(text.contains("# TYPE sigs_added_total counter\nsigs_added_total 5\n"));
+    assert!(text.contains("# TYPE sigs_pruned_total counter\nsigs_pruned_total 2\n"));
+    assert!(text.contains("# TYPE sigs_evicted_total counter\nsigs_evicted_total 1\n"));
+}
+
+#[test]
+fn test_stats_candid_round_trip() {
+    let stats = SignatureMapStats {
+        num_signatures: 3,
+        num_seeds: 2,
+        oldest_expiration: Some(10),
+        newest_expiration: Some(30),
+        num_expired_pending: 1,
+        estimated_heap_bytes: 1024,
+        total_added: 5,
+        total_pruned: 2,
+        total_evicted: 1,
+    };
+    let encoded = candid::encode_one(&stats).expect("failed to encode");
+    let decoded: SignatureMapStats = candid::decode_one(&encoded).expect("failed to decode");
+    assert_eq!(decoded, stats);
+}
+
+fn sig_inputs<'a>(seed: &'a [u8], message: &'a [u8]) -> CanisterSigInputs<'a> {
+    CanisterSigInputs {
+        domain: DOMAIN,
+        seed,
+        message,
+    }
+}
+
+#[test]
+fn test_capacity_limit_rejects() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures: Some(2),
+        ..Default::default()
+    });
+
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[2], &[2]), TIME_NOW)
+        .expect("failed to add signature");
+    let result = map.add_signature_internal(&sig_inputs(&[3], &[3]), TIME_NOW);
+    assert_matches!(
+        result,
+        Err(CanisterSigError::CapacityExceeded { max_signatures: 2 })
+    );
+    assert!(map
+        .witness(&[3], sig_inputs(&[3], &[3]).message_hash())
+        .is_none());
+
+    // re-adding an existing signature does not need additional capacity
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to re-add signature");
+
+    // once the signatures are pruned, there is capacity again
+    map.add_signature_internal(&sig_inputs(&[3], &[3]), TIME_NOW + 2 * MINUTE_NS)
+        .expect("failed to add signature");
+}
+
+#[test]
+fn test_capacity_limit_evicts_soonest_expiring() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures: Some(2),
+        policy: LimitPolicy::EvictSoonestExpiring,
+        ..Default::default()
+    });
+
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[2], &[2]), TIME_NOW + 1)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[3], &[3]), TIME_NOW + 2)
+        .expect("failed to add signature");
+
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_none());
+    assert!(map
+        .witness(&[2], sig_inputs(&[2], &[2]).message_hash())
+        .is_some());
+    assert!(map
+        .witness(&[3], sig_inputs(&[3], &[3]).message_hash())
+        .is_some());
+    assert_eq!(map.stats_internal(TIME_NOW).num_signatures, 2);
+    assert_eq!(map.stats_internal(TIME_NOW).total_evicted, 1);
+}
+
+#[test]
+fn test_seed_quota_rejects() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures_per_seed: Some(2),
+        ..Default::default()
+    });
+
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[1], &[2]), TIME_NOW)
+        .expect("failed to add signature");
+    let result = map.add_signature_internal(&sig_inputs(&[1], &[3]), TIME_NOW);
+    assert_matches!(
+        result,
+        Err(CanisterSigError::SeedQuotaExceeded {
+            max_signatures_per_seed: 2
+        })
+    );
+
+    // other seeds are not affected
+    map.add_signature_internal(&sig_inputs(&[2], &[3]), TIME_NOW)
+        .expect("failed to add signature");
+}
+
+#[test]
+fn test_seed_quota_evicts_soonest_expiring_of_same_seed() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures_per_seed: Some(2),
+        policy: LimitPolicy::EvictSoonestExpiring,
+        ..Default::default()
+    });
+
+    map.add_signature_internal(&sig_inputs(&[2], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW + 1)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[1], &[2]), TIME_NOW + 2)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[1], &[3]), TIME_NOW + 3)
+        .expect("failed to add signature");
+
+    assert!(map
+        .witness(&[2], sig_inputs(&[2], &[1]).message_hash())
+        .is_some());
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_none());
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[2]).message_hash())
+        .is_some());
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[3]).message_hash())
+        .is_some());
+    assert_eq!(map.len(), 3);
+}
+
+#[test]
+fn test_zero_seed_quota_rejects_even_when_evicting() {
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures_per_seed: Some(0),
+        policy: LimitPolicy::EvictSoonestExpiring,
+        ..Default::default()
+    });
+    let result = map.add_signature_internal(&sig_inputs(&[1], &[1]), 100);
+    assert_matches!(result, Err(CanisterSigError::SeedQuotaExceeded { .. }));
+}
+
+#[test]
+fn test_revoke_signature() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    let root_hash_before = map.root_hash();
+    map.add_signature_internal(&sig_inputs(&[1], &[2]), TIME_NOW)
+        .expect("failed to add signature");
+
+    let root_hash = map.revoke_signature(&sig_inputs(&[1], &[2]));
+
+    assert_eq!(root_hash, map.root_hash());
+    assert_eq!(root_hash, root_hash_before);
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[2]).message_hash())
+        .is_none());
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_some());
+    assert_eq!(map.len(), 1);
+
+    // revoking a non-existing signature is a no-op
+    assert_eq!(map.revoke_signature(&sig_inputs(&[1], &[2])), root_hash);
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn test_revoke_seed() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+    map.add_signature_internal(&sig_inputs(&[2], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    let root_hash_before = map.root_hash();
+    for message in 1..=3 {
+        map.add_signature_internal(&sig_inputs(&[1], &[message]), TIME_NOW)
+            .expect("failed to add signature");
+    }
+
+    let root_hash = map.revoke_seed(&[1]);
+
+    assert_eq!(root_hash, root_hash_before);
+    for message in 1..=3 {
+        assert!(map
+            .witness(&[1], sig_inputs(&[1], &[message]).message_hash())
+            .is_none());
+    }
+    assert!(map
+        .witness(&[2], sig_inputs(&[2], &[1]).message_hash())
+        .is_some());
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.stats_internal(TIME_NOW).num_signatures, 1);
+}
+
+#[test]
+fn test_revoke_domain() {
+    const TIME_NOW: u64 = 100;
+    let other_domain_inputs = CanisterSigInputs {
+        domain: SignatureDomain::from_static(b"other-domain"),
+        seed: &[1],
+        message: &[1],
+    };
+    let mut map = SignatureMap::default();
+    map.add_signature_internal(&other_domain_inputs, TIME_NOW)
+        .expect("failed to add signature");
+    let root_hash_before = map.root_hash();
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[2], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+
+    let root_hash = map.revoke_domain(DOMAIN);
+
+    assert_eq!(root_hash, root_hash_before);
+    assert!(map
+        .witness(&[1], other_domain_inputs.message_hash())
+        .is_some());
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_none());
+    assert!(map
+        .witness(&[2], sig_inputs(&[2], &[1]).message_hash())
+        .is_none());
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn test_revoked_signatures_are_not_pruned() {
+    let mut map = SignatureMap::default();
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(2), 10);
+    map.delete(hash_bytes(seed(1)), message(1));
+
+    assert_eq!(map.len(), 1);
+    assert_eq!(1, map.prune_expired(/*time now*/ 20));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_add_signature_reports_new_and_refreshed() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+
+    let added = map
+        .add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    assert_eq!(
+        added,
+        AddedSignature::New {
+            expires_at: TIME_NOW + MINUTE_NS
+        }
+    );
+
+    let added = map
+        .add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW + 10)
+        .expect("failed to add signature");
+    assert_eq!(
+        added,
+        AddedSignature::Refreshed {
+            expires_at: TIME_NOW + 10 + MINUTE_NS
+        }
+    );
+    assert_eq!(added.expires_at(), TIME_NOW + 10 + MINUTE_NS);
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.stats_internal(TIME_NOW).total_added, 1);
+}
+
+#[test]
+fn test_add_signature_until_caps_expiration() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+
+    let added = map
+        .add_signature_until_internal(&sig_inputs(&[1], &[1]), TIME_NOW + 10, TIME_NOW)
+        .expect("failed to add signature");
+    assert_eq!(
+        added,
+        AddedSignature::New {
+            expires_at: TIME_NOW + 10
+        }
+    );
+
+    let added = map
+        .add_signature_until_internal(&sig_inputs(&[1], &[2]), u64::MAX, TIME_NOW)
+        .expect("failed to add signature");
+    assert_eq!(added.expires_at(), TIME_NOW + MINUTE_NS);
+
+    assert_eq!(1, map.prune_expired(TIME_NOW + 10));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn test_add_signature_until_rejects_past_expiration() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+
+    let result = map.add_signature_until_internal(&sig_inputs(&[1], &[1]), TIME_NOW, TIME_NOW);
+    assert_matches!(
+        result,
+        Err(CanisterSigError::Expired {
+            expires_at: TIME_NOW
+        })
+    );
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_refreshed_signature_is_not_pruned_early() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW + MINUTE_NS / 2)
+        .expect("failed to add signature");
+
+    // the first expiration has passed, but the signature was refreshed
+    assert_eq!(0, map.prune_expired(TIME_NOW + MINUTE_NS));
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_some());
+    assert_eq!(map.len(), 1);
+
+    assert_eq!(1, map.prune_expired(TIME_NOW + MINUTE_NS / 2 + MINUTE_NS));
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_none());
+    assert!(map.is_empty());
+}
+
+#[test]
+fn test_refresh_never_shortens_expiration() {
+    let mut map = SignatureMap::default();
+    assert_eq!(
+        map.put(DOMAIN, hash_bytes(seed(1)), message(1), 20),
+        AddedSignature::New { expires_at: 20 }
+    );
+    assert_eq!(
+        map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10),
+        AddedSignature::Refreshed { expires_at: 20 }
+    );
+
+    assert_eq!(0, map.prune_expired(/*time now*/ 15));
+    assert_eq!(1, map.prune_expired(/*time now*/ 20));
+}
+
+#[test]
+fn test_refresh_replaces_expiration_index_entry() {
+    let mut map = SignatureMap::default();
+    for i in 0..100 {
+        map.put(DOMAIN, hash_bytes(seed(0)), message(0), i);
+    }
+
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.expirations.len(), 1);
+    assert_eq!(0, map.prune_expired(/*time now*/ 98));
+    assert_eq!(1, map.prune_expired(/*time now*/ 99));
+    assert!(map.is_empty());
+    assert!(map.expirations.is_empty());
+}
+
+#[test]
+fn test_capacity_eviction_skips_refreshed_signatures() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::with_limits(SignatureMapLimits {
+        max_signatures: Some(2),
+        policy: LimitPolicy::EvictSoonestExpiring,
+        ..Default::default()
+    });
+
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[2], &[2]), TIME_NOW + 1)
+        .expect("failed to add signature");
+    // refreshing makes [1] expire after [2]
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW + 2)
+        .expect("failed to add signature");
+    map.add_signature_internal(&sig_inputs(&[3], &[3]), TIME_NOW + 3)
+        .expect("failed to add signature");
+
+    assert!(map
+        .witness(&[1], sig_inputs(&[1], &[1]).message_hash())
+        .is_some());
+    assert!(map
+        .witness(&[2], sig_inputs(&[2], &[2]).message_hash())
+        .is_none());
+    assert!(map
+        .witness(&[3], sig_inputs(&[3], &[3]).message_hash())
+        .is_some());
+}
+
+#[test]
+fn test_signature_expiration_lookup() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+
+    assert_eq!(
+        map.signature_expiration(&sig_inputs(&[1], &[1])),
+        Some(TIME_NOW + MINUTE_NS)
+    );
+    assert_eq!(map.signature_expiration(&sig_inputs(&[1], &[2])), None);
+}
+
+#[test]
+fn test_expired_signature_retrievable_until_pruned_by_default() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+
+    let result = map.get_signature_as_cbor_internal(
+        &sig_inputs(&[1], &[1]),
+        vec![1, 2, 3],
+        None,
+        TIME_NOW + 2 * MINUTE_NS,
+    );
+    assert_matches!(result, Ok(_));
+}
+
+#[test]
+fn test_strict_expiry_rejects_expired_signature() {
+    const TIME_NOW: u64 = 100;
+    let mut map = SignatureMap::default();
+    map.set_strict_expiry(true);
+    map.add_signature_internal(&sig_inputs(&[1], &[1]), TIME_NOW)
+        .expect("failed to add signature");
+
+    let result = map.get_signature_as_cbor_internal(
+        &sig_inputs(&[1], &[1]),
+        vec![1, 2, 3],
+        None,
+        TIME_NOW + MINUTE_NS - 1,
+    );
+    assert_matches!(result, Ok(_));
+
+    let result = map.get_signature_as_cbor_internal(
+        &sig_inputs(&[1], &[1]),
+        vec![1, 2, 3],
+        None,
+        TIME_NOW + MINUTE_NS,
+    );
+    assert_matches!(
+        result,
+        Err(CanisterSigError::Expired { expires_at }) if expires_at == TIME_NOW + MINUTE_NS
+    );
+
+    let result =
+        map.get_signature_as_cbor_internal(&sig_inputs(&[1], &[2]), vec![1, 2, 3], None, TIME_NOW);
     assert_matches!(result, Err(CanisterSigError::NoSignature));
 }
+
+#[test]
+fn test_next_expiration() {
+    let mut map = SignatureMap::default();
+    assert_eq!(map.next_expiration(), None);
+
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 20);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(2), 10);
+    assert_eq!(map.next_expiration(), Some(10));
+
+    map.prune_expired(/*time now*/ 10);
+    assert_eq!(map.next_expiration(), Some(20));
+}
+
+#[test]
+fn test_signature_leaf_hash() {
+    let entry = SigEntry::default();
+    assert_eq!(entry.root_hash(), ic_certification::leaf_hash(b""));
+    assert_eq!(entry.root_hash(), entry.as_hash_tree().digest());
+}
+
+#[test]
+fn test_refresh_does_not_change_root_hash() {
+    let mut map = SignatureMap::default();
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(2), 20);
+    let root_hash = map.root_hash();
+
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 30);
+
+    assert_eq!(map.root_hash(), root_hash);
+    assert_eq!(map.next_expiration(), Some(20));
+    assert_consistent(&map);
+}
+
+#[test]
+fn test_delete_removes_expiration() {
+    let mut map = SignatureMap::default();
+    map.put(DOMAIN, hash_bytes(seed(1)), message(1), 10);
+    map.put(DOMAIN, hash_bytes(seed(2)), message(2), 20);
+
+    map.delete(hash_bytes(seed(1)), message(1));
+
+    assert_eq!(map.next_expiration(), Some(20));
+    assert_eq!(map.stats_internal(0).oldest_expiration, Some(20));
+    assert_consistent(&map);
+}
+
+// `rand` is a dev-dependency on native targets only, see Cargo.toml.
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_indexes_consistent_with_random_operations() {
+    use rand::prelude::*;
+
+    let mut map = SignatureMap::default();
+    let mut rng = rand::rng();
+
+    for round in 0..10_000u64 {
+        let seed = seed(rng.random_range(0..100));
+        let message = message(rng.random_range(0..100));
+        match rng.random_range(0..10) {
+            0 => map.delete(hash_bytes(seed), message),
+            1 => {
+                map.revoke_seed(&seed);
+            }
+            2 => {
+                map.prune_expired(round.saturating_sub(500));
+            }
+            _ => {
+                map.put(
+                    DOMAIN,
+                    hash_bytes(seed),
+                    message,
+                    round + rng.random_range(0..1000),
+                );
+            }
+        }
+    }
+
+    assert!(!map.is_empty());
+    assert_consistent(&map);
+}
+
+/// Checks that the expiration index of the map matches the signatures in the certified tree.
+fn assert_consistent(map: &SignatureMap) {
+    let mut expirations = BTreeSet::new();
+    for (seed_hash, submap) in map.certified_map.iter() {
+        for (msg_hash, entry) in submap.iter() {
+            expirations.insert((entry.expires_at.get(), *seed_hash, *msg_hash));
+        }
+    }
+    assert_eq!(expirations, map.expirations);
+    assert_eq!(expirations.len(), map.len());
+}
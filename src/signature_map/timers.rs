@@ -0,0 +1,97 @@
+//! Background pruning of a [SignatureMap] using [ic_cdk_timers].
+//!
+//! Without background pruning, expired signatures are only removed when new signatures are
+//! added. With [schedule_pruning], a timer fires at the next expiration in the map, prunes
+//! a bounded batch of expired signatures, updates the `certified_data`,
+//! and re-schedules itself, so the map shrinks even if the canister is otherwise idle.
+use super::SignatureMap;
+use ic_cdk::api::time;
+use ic_cdk_timers::{clear_timer, set_timer};
+use std::cell::RefCell;
+use std::thread::LocalKey;
+use std::time::Duration;
+
+/// Schedules pruning of the given signature map at its next expiration, unless a pruning
+/// timer is already scheduled by then. Does nothing if the map is empty.
+///
+/// `map` is the map currently borrowed from `signatures`, so this can be called while the
+/// map is borrowed, e.g. right after [SignatureMap::add_signature]. The timer borrows the map
+/// from `signatures` when it fires. After each pruning batch, `update_certified_data` is
+/// called with the pruned map, and must set the `certified_data` of the canister accordingly,
+/// e.g. to the labeled root hash of the map (cf. [super::LABEL_SIG]).
+///
+/// This should be called after every [SignatureMap::add_signature] (and in `post_upgrade`, if
+/// the map is restored from stable memory), as timers are not re-scheduled by the map itself.
+pub fn schedule_pruning(
+    map: &mut SignatureMap,
+    signatures: &'static LocalKey<RefCell<SignatureMap>>,
+    update_certified_data: fn(&SignatureMap),
+) {
+    let scheduled_at = map.pruning_timer.map(|(_, fires_at)| fires_at);
+    let Some(fires_at) = new_pruning_timer_fires_at(scheduled_at, map.next_expiration()) else {
+        return;
+    };
+    if let Some((timer_id, _)) = map.pruning_timer.take() {
+        clear_timer(timer_id);
+    }
+    let delay = Duration::from_nanos(fires_at.saturating_sub(time()));
+    let timer_id = set_timer(delay, move || {
+        signatures
+            .with_borrow_mut(|map| prune_and_reschedule(map, signatures, update_certified_data))
+    });
+    map.pruning_timer = Some((timer_id, fires_at));
+}
+
+fn prune_and_reschedule(
+    map: &mut SignatureMap,
+    signatures: &'static LocalKey<RefCell<SignatureMap>>,
+    update_certified_data: fn(&SignatureMap),
+) {
+    map.pruning_timer = None;
+    if map.prune_expired(time()) > 0 {
+        update_certified_data(map);
+    }
+    // If more expired signatures are left, the next batch is scheduled immediately.
+    schedule_pruning(map, signatures, update_certified_data);
+}
+
+/// Returns when a new pruning timer must fire, given when the pending timer (if any) fires
+/// and the next expiration in the map (if any). Returns `None` if no new timer is needed,
+/// i.e. the map is empty or the pending timer fires no later than the next expiration.
+fn new_pruning_timer_fires_at(
+    scheduled_at: Option<u64>,
+    next_expiration: Option<u64>,
+) -> Option<u64> {
+    let next_expiration = next_expiration?;
+    match scheduled_at {
+        Some(fires_at) if fires_at <= next_expiration => None,
+        _ => Some(next_expiration),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_not_schedule_timer_for_empty_map() {
+        assert_eq!(new_pruning_timer_fires_at(None, None), None);
+        assert_eq!(new_pruning_timer_fires_at(Some(10), None), None);
+    }
+
+    #[test]
+    fn should_schedule_timer_at_next_expiration_if_none_pending() {
+        assert_eq!(new_pruning_timer_fires_at(None, Some(10)), Some(10));
+    }
+
+    #[test]
+    fn should_keep_pending_timer_firing_no_later_than_next_expiration() {
+        assert_eq!(new_pruning_timer_fires_at(Some(5), Some(10)), None);
+        assert_eq!(new_pruning_timer_fires_at(Some(10), Some(10)), None);
+    }
+
+    #[test]
+    fn should_replace_pending_timer_firing_after_next_expiration() {
+        assert_eq!(new_pruning_timer_fires_at(Some(11), Some(10)), Some(10));
+    }
+}
@@ -0,0 +1,251 @@
+//! Canister signatures on structured data.
+//!
+//! Structured data (i.e. a map of named fields) is signed via its representation-independent hash,
+//! like delegations (cf. [crate::delegation_signature_msg]) and IC requests. This allows relying
+//! parties to verify a signature on human-meaningful claims by re-computing the hash from the
+//! claims, independently of how they were encoded in transit:
+//! ```
+//! use ic_canister_sig_creation::structured_data::StructuredMessage;
+//! use ic_canister_sig_creation::SignatureDomain;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct AgeClaim {
+//!     subject: String,
+//!     min_age: u64,
+//! }
+//!
+//! const CLAIM_DOMAIN: SignatureDomain = SignatureDomain::from_static(b"my-app-age-claim");
+//!
+//! let claim = AgeClaim { subject: "alice".to_string(), min_age: 18 };
+//! let message = StructuredMessage::from_serializable(&claim).expect("unsupported claim");
+//! let sig_inputs = message.sig_inputs(CLAIM_DOMAIN, b"seed");
+//! ```
+//!
+//! See https://internetcomputer.org/docs/current/references/ic-interface-spec#hash-of-map
+use crate::cbor;
+use crate::signature_map::CanisterSigInputs;
+use crate::SignatureDomain;
+use ic_certification::Hash;
+use ic_representation_independent_hash::representation_independent_hash;
+use serde::Serialize;
+use std::collections::HashSet;
+
+pub use ic_representation_independent_hash::Value;
+
+/// The message of a signature on structured data, i.e. the representation-independent hash
+/// of the data.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct StructuredMessage(Hash);
+
+impl StructuredMessage {
+    /// Constructs the message for the given fields.
+    pub fn from_values(fields: &[(String, Value)]) -> Self {
+        StructuredMessage(representation_independent_hash(fields))
+    }
+
+    /// Constructs the message for the given struct or map, see [to_values] for the supported data.
+    pub fn from_serializable<T: Serialize + ?Sized>(data: &T) -> Result<Self, String> {
+        Ok(Self::from_values(&to_values(data)?))
+    }
+
+    /// Returns the message, i.e. the representation-independent hash of the data.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the inputs of the canister signature on this message
+    /// with the given domain and seed.
+    pub fn sig_inputs<'a>(
+        &'a self,
+        domain: SignatureDomain<'a>,
+        seed: &'a [u8],
+    ) -> CanisterSigInputs<'a> {
+        CanisterSigInputs {
+            domain,
+            seed,
+            message: &self.0,
+        }
+    }
+}
+
+/// Converts the given struct or map to the fields of a representation-independent hash.
+///
+/// The representation-independent hash supports only text, unsigned numbers, byte strings
+/// and arrays thereof as values, so the fields are converted as follows:
+/// - strings and unit enum variants are converted to [Value::String],
+/// - non-negative integers are converted to [Value::Number],
+/// - byte strings (e.g. `serde_bytes::ByteBuf`) are converted to [Value::Bytes],
+///   whereas a plain `Vec<u8>` is converted to an array of numbers,
+/// - sequences and tuples are converted to [Value::Array],
+/// - fields with value `None` are omitted.
+///
+/// Any other data (e.g. booleans, floats, negative integers or nested structs) is rejected,
+/// as is data that is not a struct or a map with string keys.
+pub fn to_values<T: Serialize + ?Sized>(data: &T) -> Result<Vec<(String, Value)>, String> {
+    let cbor::Value::Map(entries) =
+        cbor::to_value(data).map_err(|e| format!("failed to serialize structured data: {}", e))?
+    else {
+        return Err("structured data must be a struct or a map".to_string());
+    };
+    let mut keys = HashSet::new();
+    let mut fields = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        let cbor::Value::Text(key) = key else {
+            return Err("structured data must have string keys".to_string());
+        };
+        if !keys.insert(key.clone()) {
+            return Err(format!("duplicate field '{}' in structured data", key));
+        }
+        if value.is_null() {
+            continue;
+        }
+        let value = to_value(value).map_err(|e| format!("invalid field '{}': {}", key, e))?;
+        fields.push((key, value));
+    }
+    Ok(fields)
+}
+
+fn to_value(value: cbor::Value) -> Result<Value, String> {
+    match value {
+        cbor::Value::Text(text) => Ok(Value::String(text)),
+        cbor::Value::Bytes(bytes) => Ok(Value::Bytes(bytes)),
+        cbor::Value::Integer(integer) => u64::try_from(integer)
+            .map(Value::Number)
+            .map_err(|_| "only unsigned 64-bit integers are supported".to_string()),
+        cbor::Value::Array(elements) => elements
+            .into_iter()
+            .map(to_value)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        cbor::Value::Bool(_) => Err("booleans are not supported".to_string()),
+        cbor::Value::Float(_) => Err("floats are not supported".to_string()),
+        cbor::Value::Null => Err("null values are not supported in arrays".to_string()),
+        cbor::Value::Map(_) => Err("nested maps are not supported".to_string()),
+        _ => Err("unsupported value".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use serde_bytes::ByteBuf;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    struct Greeting {
+        name: String,
+        message: String,
+        answer: u64,
+    }
+
+    #[derive(Serialize)]
+    enum Level {
+        Basic,
+    }
+
+    #[test]
+    fn should_hash_struct_like_value_map() {
+        let greeting = Greeting {
+            name: "foo".to_string(),
+            message: "Hello World!".to_string(),
+            answer: 42,
+        };
+        let message = StructuredMessage::from_serializable(&greeting).unwrap();
+        // Known answer from `ic-representation-independent-hash`.
+        assert_eq!(
+            hex::encode(message.as_bytes()),
+            "b0c6f9191e37dceafdfc47fbfc7e9cc95f21c7b985c2f7ba5855015c2a8f13ac"
+        );
+        assert_eq!(
+            message,
+            StructuredMessage::from_values(&[
+                ("name".into(), Value::String("foo".into())),
+                ("message".into(), Value::String("Hello World!".into())),
+                ("answer".into(), Value::Number(42)),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_convert_supported_values() {
+        #[derive(Serialize)]
+        struct Claims {
+            level: Level,
+            key: ByteBuf,
+            scores: Vec<u8>,
+            pair: (String, i32),
+            nickname: Option<String>,
+        }
+        let claims = Claims {
+            level: Level::Basic,
+            key: ByteBuf::from(vec![1, 2]),
+            scores: vec![3],
+            pair: ("a".to_string(), 4),
+            nickname: None,
+        };
+        let fields = format!("{:?}", to_values(&claims).unwrap());
+        let expected = format!(
+            "{:?}",
+            vec![
+                ("level".to_string(), Value::String("Basic".into())),
+                ("key".to_string(), Value::Bytes(vec![1, 2])),
+                ("scores".to_string(), Value::Array(vec![Value::Number(3)])),
+                (
+                    "pair".to_string(),
+                    Value::Array(vec![Value::String("a".into()), Value::Number(4)])
+                ),
+            ]
+        );
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn should_accept_string_keyed_map() {
+        let map = BTreeMap::from([("answer", 42u64)]);
+        assert_eq!(
+            StructuredMessage::from_serializable(&map).unwrap(),
+            StructuredMessage::from_values(&[("answer".into(), Value::Number(42))])
+        );
+    }
+
+    #[test]
+    fn should_reject_unsupported_data() {
+        assert_matches!(to_values(&42u64), Err(e) if e.contains("struct or a map"));
+        assert_matches!(
+            to_values(&BTreeMap::from([(1u64, 2u64)])),
+            Err(e) if e.contains("string keys")
+        );
+        assert_matches!(
+            to_values(&BTreeMap::from([("a", true)])),
+            Err(e) if e.contains("field 'a'") && e.contains("booleans")
+        );
+        assert_matches!(
+            to_values(&BTreeMap::from([("a", -1i64)])),
+            Err(e) if e.contains("unsigned")
+        );
+        assert_matches!(
+            to_values(&BTreeMap::from([("a", 1.5f64)])),
+            Err(e) if e.contains("floats")
+        );
+        assert_matches!(
+            to_values(&BTreeMap::from([("a", BTreeMap::from([("b", 1u64)]))])),
+            Err(e) if e.contains("nested maps")
+        );
+        assert_matches!(
+            to_values(&BTreeMap::from([("a", vec![None, Some(1u64)])])),
+            Err(e) if e.contains("null")
+        );
+    }
+
+    #[test]
+    fn should_create_sig_inputs() {
+        let message = StructuredMessage::from_values(&[("answer".into(), Value::Number(42))]);
+        let domain = SignatureDomain::from_static(b"test-claims");
+        let inputs = message.sig_inputs(domain, b"seed");
+        assert_eq!(inputs.domain, domain);
+        assert_eq!(inputs.seed, b"seed");
+        assert_eq!(inputs.message, message.as_bytes());
+    }
+}
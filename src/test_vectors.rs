@@ -0,0 +1,370 @@
+//! Known-answer test vectors, so that other implementations (e.g. in TypeScript or Motoko) can
+//! check their conformance with this crate.
+//!
+//! The vectors are available in machine-readable form in [TEST_VECTORS_JSON]. All byte strings
+//! are hex-encoded, principals are given in their textual representation and 64-bit numbers as
+//! decimal strings. The JSON file is generated by [generate] and checked by the tests of this
+//! module; run them with the environment variable `UPDATE_TEST_VECTORS` set to regenerate it.
+use crate::signature_map::{CanisterSigInputs, SignatureMap, LABEL_SIG};
+use crate::{
+    cbor, delegation_signature_msg, hash_bytes, hash_with_domain, CanisterSigPublicKey,
+    SignatureDomain,
+};
+use candid::Principal;
+use ic_certification::{labeled, labeled_hash, leaf, Certificate};
+use serde::{Deserialize, Serialize};
+
+/// The test vectors, as JSON.
+pub const TEST_VECTORS_JSON: &str = include_str!("test_vectors.json");
+
+/// The canister id of [CANISTER_SIG_PK_DER].
+pub const TEST_SIGNING_CANISTER_ID: &str = "rwlgt-iiaaa-aaaaa-aaaaa-cai";
+/// The seed of [CANISTER_SIG_PK_DER].
+pub const TEST_SEED: [u8; 3] = [42, 72, 44];
+/// A DER-encoded canister signature public key.
+pub const CANISTER_SIG_PK_DER: &[u8; 33] = b"\x30\x1f\x30\x0c\x06\x0a\x2b\x06\x01\x04\x01\x83\xb8\x43\x01\x02\x03\x0f\x00\x0a\x00\x00\x00\x00\x00\x00\x00\x00\x01\x01\x2a\x48\x2c";
+/// A CBOR-encoded canister signature, as returned by a canister.
+pub const CANISTER_SIG_CBOR: &[u8; 265] = b"\xd9\xd9\xf7\xa2\x6b\x63\x65\x72\x74\x69\x66\x69\x63\x61\x74\x65\x58\xa1\xd9\xd9\xf7\xa2\x64\x74\x72\x65\x65\x83\x01\x83\x02\x48\x63\x61\x6e\x69\x73\x74\x65\x72\x83\x02\x4a\x00\x00\x00\x00\x00\x00\x00\x01\x01\x01\x83\x02\x4e\x63\x65\x72\x74\x69\x66\x69\x65\x64\x5f\x64\x61\x74\x61\x82\x03\x58\x20\xa9\xea\x05\x9d\xf2\x7a\x09\x7e\xc4\x38\xdb\x35\x62\xb9\x55\xc3\xd3\xfa\x08\xeb\x17\xc1\x3c\xda\x63\x90\x42\xfa\xe0\xcf\x60\x36\x83\x02\x44\x74\x69\x6d\x65\x82\x03\x43\x87\xad\x4b\x69\x73\x69\x67\x6e\x61\x74\x75\x72\x65\x58\x30\xa4\xd5\xfd\x47\xa0\x88\x13\x5b\xed\x52\x22\x0c\xca\xa4\x76\xfb\x6c\x88\x95\xdd\xa3\x1e\x2a\x86\xa7\xa2\x97\xdc\x7a\x30\x81\x27\x1e\xf1\x1a\xee\xb5\xd2\xbb\x25\x83\x0d\xcb\xdd\x82\xad\x7a\x52\x64\x74\x72\x65\x65\x83\x02\x43\x73\x69\x67\x83\x02\x58\x20\x00\x42\xcd\x04\x7a\xad\x32\x06\x37\xce\xae\xe2\x1d\x48\x9e\xf4\xe5\x14\xce\x20\x1f\x19\x60\x68\x30\xa2\xaf\x7b\x7d\x9c\x86\x7d\x83\x02\x58\x20\x14\x9b\x80\x95\x11\x98\x27\xcf\xea\x0a\xa6\x6e\x7b\x7f\x80\xe9\x13\xca\xef\xa3\x1a\x60\x6d\xe4\x02\x69\xc3\xd8\x6c\xfe\xa5\x8d\x82\x03\x40";
+
+/// The time at which the signatures of the vectors are created, in nanoseconds since the UNIX epoch.
+const NOW: u64 = 1_697_000_000_000_000_000;
+const ED25519_PK_DER: &[u8] = b"\x30\x2a\x30\x05\x06\x03\x2b\x65\x70\x03\x21\x00\xf2\xa8\xdc\x6a\x5b\x0b\xbe\x2b\x07\xdd\x8c\x0e\xbe\x6c\x3f\x4b\x4c\x1b\xf7\xcc\x2b\xc8\x6b\x4e\x0d\x3e\x0a\x70\xb7\xa1\x3e\x65";
+const CUSTOM_DOMAIN: SignatureDomain = SignatureDomain::from_static(b"my-app-domain");
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub public_keys: Vec<PublicKeyVector>,
+    pub delegation_messages: Vec<DelegationMessageVector>,
+    pub domain_hashes: Vec<DomainHashVector>,
+    pub witnesses: Vec<WitnessVector>,
+    pub signatures: Vec<SignatureVector>,
+}
+
+/// The encodings of a canister signature public key, see [CanisterSigPublicKey].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PublicKeyVector {
+    pub canister_id: String,
+    pub seed: String,
+    pub der: String,
+    pub raw: String,
+    pub cose: String,
+    pub pem: String,
+    pub base64url: String,
+    /// The self-authenticating principal of the key.
+    pub principal: String,
+}
+
+/// The signing input of a delegation, see [delegation_signature_msg].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DelegationMessageVector {
+    pub pubkey: String,
+    pub expiration: String,
+    pub targets: Option<Vec<String>>,
+    pub message: String,
+}
+
+/// A domain-separated hash, see [hash_with_domain].
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DomainHashVector {
+    /// The domain separator, as text.
+    pub domain: String,
+    pub message: String,
+    pub hash: String,
+}
+
+/// The witness of a signature in a [SignatureMap] containing the signatures of all witness vectors.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct WitnessVector {
+    /// The domain separator, as text.
+    pub domain: String,
+    pub seed: String,
+    pub message: String,
+    pub seed_hash: String,
+    /// The domain-separated hash of the message.
+    pub message_hash: String,
+    /// The root hash of the signature map.
+    pub root_hash: String,
+    /// The CBOR-encoded hash tree.
+    pub witness: String,
+}
+
+/// A complete canister signature, as returned by a canister. The certificate certifies the
+/// signature map, but its BLS signature is not valid, as it depends on the keys of the IC.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SignatureVector {
+    /// The DER-encoded public key of the signature.
+    pub public_key: String,
+    /// The domain separator, as text.
+    pub domain: String,
+    pub message: String,
+    /// The `certified_data` of the signing canister.
+    pub certified_data: String,
+    /// The CBOR-encoded signature.
+    pub signature: String,
+}
+
+impl TestVectors {
+    /// Parses the vectors from [TEST_VECTORS_JSON].
+    pub fn load() -> Self {
+        serde_json::from_str(TEST_VECTORS_JSON).expect("invalid test vectors JSON")
+    }
+
+    /// Returns the vectors as pretty-printed JSON, in the format of [TEST_VECTORS_JSON].
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("failed to encode test vectors") + "\n"
+    }
+}
+
+/// Computes the test vectors with this crate.
+pub fn generate() -> TestVectors {
+    TestVectors {
+        public_keys: public_keys().iter().map(public_key_vector).collect(),
+        delegation_messages: delegation_message_vectors(),
+        domain_hashes: domain_hash_vectors(),
+        witnesses: witness_vectors(),
+        signatures: signature_vectors(),
+    }
+}
+
+fn public_keys() -> Vec<CanisterSigPublicKey> {
+    vec![
+        CanisterSigPublicKey::new(principal(TEST_SIGNING_CANISTER_ID), TEST_SEED.to_vec()),
+        CanisterSigPublicKey::new(principal("ryjl3-tyaaa-aaaaa-aaaba-cai"), vec![]),
+        CanisterSigPublicKey::new(
+            principal("rdmx6-jaaaa-aaaaa-aaadq-cai"),
+            hash_bytes(b"seed").to_vec(),
+        ),
+    ]
+}
+
+fn public_key_vector(public_key: &CanisterSigPublicKey) -> PublicKeyVector {
+    PublicKeyVector {
+        canister_id: public_key.canister_id.to_text(),
+        seed: hex::encode(&public_key.seed),
+        der: hex::encode(public_key.to_der()),
+        raw: hex::encode(public_key.to_raw()),
+        cose: hex::encode(public_key.to_cose()),
+        pem: public_key.to_pem(),
+        base64url: public_key.to_base64url(),
+        principal: public_key.to_principal().to_text(),
+    }
+}
+
+fn delegation_message_vectors() -> Vec<DelegationMessageVector> {
+    let targets = [
+        None,
+        Some(vec![principal(TEST_SIGNING_CANISTER_ID)]),
+        Some(vec![
+            principal("ryjl3-tyaaa-aaaaa-aaaba-cai"),
+            principal("rdmx6-jaaaa-aaaaa-aaadq-cai"),
+        ]),
+    ];
+    targets
+        .into_iter()
+        .map(|targets| DelegationMessageVector {
+            pubkey: hex::encode(ED25519_PK_DER),
+            expiration: NOW.to_string(),
+            targets: targets
+                .as_ref()
+                .map(|targets| targets.iter().map(Principal::to_text).collect()),
+            message: hex::encode(delegation_signature_msg(
+                ED25519_PK_DER,
+                NOW,
+                targets.as_deref(),
+            )),
+        })
+        .collect()
+}
+
+fn domain_hash_vectors() -> Vec<DomainHashVector> {
+    let inputs: [(SignatureDomain, &[u8]); 5] = [
+        (SignatureDomain::IC_REQUEST, b""),
+        (SignatureDomain::IC_REQUEST, b"\x01\x02\x03"),
+        (SignatureDomain::IC_REQUEST_AUTH_DELEGATION, b"delegation"),
+        (SignatureDomain::IC_STATE_ROOT, &[0; 32]),
+        (CUSTOM_DOMAIN, b"hello"),
+    ];
+    inputs
+        .into_iter()
+        .map(|(domain, message)| DomainHashVector {
+            domain: domain_text(domain),
+            message: hex::encode(message),
+            hash: hex::encode(hash_with_domain(domain, message)),
+        })
+        .collect()
+}
+
+/// The inputs of the signatures in the signature map of the witness and signature vectors.
+fn signature_inputs() -> Vec<(SignatureDomain<'static>, Vec<u8>, Vec<u8>)> {
+    vec![
+        (
+            SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+            b"seed-1".to_vec(),
+            delegation_signature_msg(ED25519_PK_DER, NOW, None),
+        ),
+        (
+            SignatureDomain::IC_REQUEST,
+            b"seed-2".to_vec(),
+            hash_bytes(b"request").to_vec(),
+        ),
+        (CUSTOM_DOMAIN, b"seed-1".to_vec(), b"hello".to_vec()),
+    ]
+}
+
+fn signature_map() -> SignatureMap {
+    let mut signatures = SignatureMap::default();
+    for (domain, seed, message) in signature_inputs() {
+        let sig_inputs = CanisterSigInputs {
+            domain,
+            seed: &seed,
+            message: &message,
+        };
+        signatures
+            .add_signature_internal(&sig_inputs, NOW)
+            .expect("failed to add signature");
+    }
+    signatures
+}
+
+fn witness_vectors() -> Vec<WitnessVector> {
+    let signatures = signature_map();
+    signature_inputs()
+        .into_iter()
+        .map(|(domain, seed, message)| {
+            let message_hash = hash_with_domain(domain, &message);
+            let witness = signatures
+                .witness(&seed, message_hash)
+                .expect("missing signature");
+            WitnessVector {
+                domain: domain_text(domain),
+                seed: hex::encode(&seed),
+                message: hex::encode(&message),
+                seed_hash: hex::encode(hash_bytes(&seed)),
+                message_hash: hex::encode(message_hash),
+                root_hash: hex::encode(signatures.root_hash()),
+                witness: hex::encode(cbor::to_vec(&witness).expect("failed to encode witness")),
+            }
+        })
+        .collect()
+}
+
+fn signature_vectors() -> Vec<SignatureVector> {
+    let canister_id = principal(TEST_SIGNING_CANISTER_ID);
+    let signatures = signature_map();
+    let certified_data = labeled_hash(LABEL_SIG, &signatures.root_hash());
+    let certificate = Certificate {
+        tree: labeled(
+            b"canister".to_vec(),
+            labeled(
+                canister_id.as_slice().to_vec(),
+                labeled(b"certified_data".to_vec(), leaf(certified_data.to_vec())),
+            ),
+        ),
+        signature: vec![0; 48],
+        delegation: None,
+    };
+    let certificate =
+        cbor::to_vec_self_describing(&certificate).expect("failed to encode certificate");
+    signature_inputs()
+        .into_iter()
+        .map(|(domain, seed, message)| {
+            let sig_inputs = CanisterSigInputs {
+                domain,
+                seed: &seed,
+                message: &message,
+            };
+            let signature = signatures
+                .get_signature_as_cbor_internal(&sig_inputs, certificate.clone(), None, NOW)
+                .expect("failed to get signature");
+            SignatureVector {
+                public_key: hex::encode(CanisterSigPublicKey::new(canister_id, seed).to_der()),
+                domain: domain_text(domain),
+                message: hex::encode(&message),
+                certified_data: hex::encode(certified_data),
+                signature: hex::encode(signature),
+            }
+        })
+        .collect()
+}
+
+fn principal(text: &str) -> Principal {
+    Principal::from_text(text).expect("invalid principal")
+}
+
+fn domain_text(domain: SignatureDomain) -> String {
+    String::from_utf8(domain.as_bytes().to_vec()).expect("non-UTF-8 domain")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_canister_sig_cbor;
+
+    #[test]
+    fn should_match_generated_vectors() {
+        let json = generate().to_json();
+        if std::env::var("UPDATE_TEST_VECTORS").is_ok() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test_vectors.json");
+            std::fs::write(path, &json).expect("failed to write test vectors");
+            return;
+        }
+        assert_eq!(
+            json, TEST_VECTORS_JSON,
+            "test vectors are outdated, run the tests with UPDATE_TEST_VECTORS set"
+        );
+    }
+
+    #[test]
+    fn should_parse_public_key_vectors() {
+        let vectors = TestVectors::load();
+        assert_eq!(vectors.public_keys[0].der, hex::encode(CANISTER_SIG_PK_DER));
+        for vector in vectors.public_keys {
+            let public_key =
+                CanisterSigPublicKey::try_from(hex::decode(&vector.der).unwrap().as_slice())
+                    .unwrap();
+            assert_eq!(public_key.canister_id.to_text(), vector.canister_id);
+            assert_eq!(hex::encode(&public_key.seed), vector.seed);
+            let raw = hex::decode(&vector.raw).unwrap();
+            assert_eq!(
+                CanisterSigPublicKey::try_from_raw(&raw),
+                Ok(public_key.clone())
+            );
+            let cose = hex::decode(&vector.cose).unwrap();
+            assert_eq!(
+                CanisterSigPublicKey::from_cose(&cose),
+                Ok(public_key.clone())
+            );
+            assert_eq!(
+                CanisterSigPublicKey::from_pem(&vector.pem),
+                Ok(public_key.clone())
+            );
+            assert_eq!(
+                CanisterSigPublicKey::from_base64url(&vector.base64url),
+                Ok(public_key.clone())
+            );
+            assert_eq!(
+                Principal::self_authenticating(hex::decode(&vector.der).unwrap()).to_text(),
+                vector.principal
+            );
+        }
+    }
+
+    #[test]
+    fn should_check_signature_vectors() {
+        let vectors = TestVectors::load();
+        for (witness, signature) in vectors.witnesses.iter().zip(&vectors.signatures) {
+            let signature_cbor = hex::decode(&signature.signature).unwrap();
+            let parsed = parse_canister_sig_cbor(&signature_cbor).unwrap();
+            assert_eq!(
+                hex::encode(parsed.tree().digest()),
+                signature.certified_data
+            );
+            let witness_cbor = hex::decode(&witness.witness).unwrap();
+            let witness_tree: ic_certification::HashTree = cbor::from_slice(&witness_cbor).unwrap();
+            assert_eq!(hex::encode(witness_tree.digest()), witness.root_hash);
+            assert_eq!(parsed.tree(), &labeled(LABEL_SIG, witness_tree));
+        }
+        let parsed = parse_canister_sig_cbor(CANISTER_SIG_CBOR).unwrap();
+        assert_eq!(parsed.to_cbor().unwrap(), CANISTER_SIG_CBOR);
+    }
+}
@@ -0,0 +1,795 @@
+//! Authentication of IC ingress requests, as performed by the IC, e.g. for off-chain gateways.
+//!
+//! The verifier checks the expiry of the request, walks the delegation chain (checking the
+//! expiration and targets of each delegation) and verifies all signatures, see
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec#authentication
+//!
+//! Canister signatures (both on delegations and on requests) are verified by this crate,
+//! including the validation of their certificates. The underlying cryptographic primitives
+//! are provided by the caller via a [SignatureVerifier], so that the crate does not depend
+//! on any particular cryptographic library.
+use crate::delegation::{is_target_allowed, validate_delegation_targets, Delegation};
+use crate::ingress::Envelope;
+use crate::session_key::{SessionKeyAlgorithm, SessionPublicKey};
+use crate::signature_map::LABEL_SIG;
+use crate::{
+    cbor, extract_raw_root_pk_from_der, hash_bytes, hash_with_domain, parse_canister_sig_cbor,
+    parse_certificate_cbor, CanisterSigPublicKey, SignatureDomain, IC_ROOT_PUBLIC_KEY,
+};
+use candid::Principal;
+use ic_certification::{Certificate, LookupResult};
+use serde_bytes::ByteBuf;
+use std::collections::HashSet;
+
+/// The maximum number of delegations in the delegation chain of a request accepted by the IC.
+pub const MAX_DELEGATIONS: usize = 20;
+/// The maximum time between the current time and the `ingress_expiry` of a request.
+pub const MAX_INGRESS_TTL_NS: u64 = 5 * 60 * 1_000_000_000;
+/// The tolerated clock drift when checking the `ingress_expiry` of a request.
+pub const PERMITTED_DRIFT_NS: u64 = 60 * 1_000_000_000;
+
+/// The cryptographic primitives needed to authenticate requests.
+pub trait SignatureVerifier {
+    /// Verifies the BLS12-381 signature (in G1) of the given message
+    /// by the given raw (96 bytes, G2) public key, as used in IC certificates.
+    fn verify_bls_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String>;
+
+    /// Verifies the signature of the given (domain-separated) message by the given key.
+    /// This is never called for canister signature keys, which are handled by the crate.
+    fn verify_basic_signature(
+        &self,
+        public_key: &SessionPublicKey,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String>;
+}
+
+/// Verifies the canister signature `signature_cbor` of the given message in the given domain
+/// by the given key. The certificate of the signature must be signed (possibly via a subnet
+/// delegation) by the given raw root public key, e.g. [IC_ROOT_PUBLIC_KEY].
+pub fn verify_canister_sig(
+    verifier: &impl SignatureVerifier,
+    root_public_key: &[u8],
+    public_key: &CanisterSigPublicKey,
+    domain: SignatureDomain,
+    message: &[u8],
+    signature_cbor: &[u8],
+) -> Result<(), String> {
+    let signature = parse_canister_sig_cbor(signature_cbor)?;
+    let seed_hash = hash_bytes(&public_key.seed);
+    let message_hash = hash_with_domain(domain, message);
+    match signature
+        .tree()
+        .lookup_path([LABEL_SIG, &seed_hash[..], &message_hash[..]])
+    {
+        LookupResult::Found([]) => {}
+        _ => return Err("signature not found in canister signature tree".to_string()),
+    }
+
+    let certificate = parse_certificate_cbor(signature.certificate())
+        .map_err(|e| format!("invalid canister signature certificate: {}", e))?;
+    verify_certificate(
+        verifier,
+        root_public_key,
+        &certificate,
+        &public_key.canister_id,
+    )?;
+    let certified_data_path = [
+        &b"canister"[..],
+        public_key.canister_id.as_slice(),
+        b"certified_data",
+    ];
+    match certificate.tree.lookup_path(certified_data_path) {
+        LookupResult::Found(certified_data) if certified_data == signature.tree().digest() => {
+            Ok(())
+        }
+        LookupResult::Found(_) => {
+            Err("certified data does not match canister signature tree".to_string())
+        }
+        _ => Err("certified data not found in canister signature certificate".to_string()),
+    }
+}
+
+/// Verifies the signature of the certificate and, if present, its subnet delegation,
+/// checking that the subnet is authoritative for the given canister.
+fn verify_certificate(
+    verifier: &impl SignatureVerifier,
+    root_public_key: &[u8],
+    certificate: &Certificate,
+    canister_id: &Principal,
+) -> Result<(), String> {
+    let public_key = match &certificate.delegation {
+        None => root_public_key.to_vec(),
+        Some(delegation) => {
+            let subnet_certificate = parse_certificate_cbor(&delegation.certificate)
+                .map_err(|e| format!("invalid subnet delegation certificate: {}", e))?;
+            if subnet_certificate.delegation.is_some() {
+                return Err("nested subnet delegations are not allowed".to_string());
+            }
+            verify_certificate_signature(verifier, root_public_key, &subnet_certificate)?;
+
+            let subnet_path =
+                |label: &'static [u8]| [&b"subnet"[..], delegation.subnet_id.as_slice(), label];
+            let LookupResult::Found(ranges_cbor) = subnet_certificate
+                .tree
+                .lookup_path(subnet_path(b"canister_ranges"))
+            else {
+                return Err("canister ranges not found in subnet delegation".to_string());
+            };
+            let ranges: Vec<(ByteBuf, ByteBuf)> = cbor::from_slice(ranges_cbor)
+                .map_err(|e| format!("failed to parse canister ranges: {}", e))?;
+            let canister_id = canister_id.as_slice();
+            if !ranges.iter().any(|(start, end)| {
+                start.as_slice() <= canister_id && canister_id <= end.as_slice()
+            }) {
+                return Err("subnet is not authorized for the signing canister".to_string());
+            }
+
+            let LookupResult::Found(subnet_public_key) = subnet_certificate
+                .tree
+                .lookup_path(subnet_path(b"public_key"))
+            else {
+                return Err("subnet public key not found in subnet delegation".to_string());
+            };
+            extract_raw_root_pk_from_der(subnet_public_key)?
+        }
+    };
+    verify_certificate_signature(verifier, &public_key, certificate)
+}
+
+fn verify_certificate_signature(
+    verifier: &impl SignatureVerifier,
+    public_key: &[u8],
+    certificate: &Certificate,
+) -> Result<(), String> {
+    let message = domain_separated(SignatureDomain::IC_STATE_ROOT, &certificate.tree.digest());
+    verifier
+        .verify_bls_signature(public_key, &message, &certificate.signature)
+        .map_err(|e| format!("invalid certificate signature: {}", e))
+}
+
+/// Returns the given message prefixed with the length-prefixed domain, i.e. the input
+/// of the signature algorithm for signatures in the given domain.
+fn domain_separated(domain: SignatureDomain, message: &[u8]) -> Vec<u8> {
+    let domain = domain.as_bytes();
+    let mut bytes = Vec::with_capacity(1 + domain.len() + message.len());
+    // The length fits into a byte, as guaranteed by `SignatureDomain`.
+    bytes.push(domain.len() as u8);
+    bytes.extend(domain);
+    bytes.extend(message);
+    bytes
+}
+
+/// Authenticates IC ingress requests.
+pub struct IngressVerifier<V> {
+    verifier: V,
+    root_public_key: Vec<u8>,
+}
+
+impl<V: SignatureVerifier> IngressVerifier<V> {
+    /// Constructs a verifier for requests to the IC mainnet, i.e. with [IC_ROOT_PUBLIC_KEY].
+    pub fn new(verifier: V) -> Self {
+        Self::with_root_public_key(verifier, IC_ROOT_PUBLIC_KEY.clone())
+    }
+
+    /// Constructs a verifier for requests to the IC instance with the given raw root public key,
+    /// e.g. a local replica.
+    pub fn with_root_public_key(verifier: V, root_public_key: Vec<u8>) -> Self {
+        IngressVerifier {
+            verifier,
+            root_public_key,
+        }
+    }
+
+    /// Authenticates the given request at time `now` (in nanoseconds since the UNIX epoch)
+    /// and returns the authenticated sender.
+    ///
+    /// The targets of the delegations are checked against the canister id of calls and queries.
+    /// As the effective canister id of a read_state request is not part of the request,
+    /// such requests are rejected if a delegation is restricted to targets,
+    /// see [IngressVerifier::verify_for_canister].
+    pub fn verify(&self, envelope: &Envelope, now: u64) -> Result<Principal, String> {
+        let canister_id = envelope.content.canister_id().copied();
+        self.verify_internal(envelope, canister_id.as_ref(), now)
+    }
+
+    /// Like [IngressVerifier::verify], but checks the targets of the delegations
+    /// against the given effective canister id, e.g. the one of a read_state request.
+    pub fn verify_for_canister(
+        &self,
+        envelope: &Envelope,
+        effective_canister_id: &Principal,
+        now: u64,
+    ) -> Result<Principal, String> {
+        self.verify_internal(envelope, Some(effective_canister_id), now)
+    }
+
+    fn verify_internal(
+        &self,
+        envelope: &Envelope,
+        canister_id: Option<&Principal>,
+        now: u64,
+    ) -> Result<Principal, String> {
+        let content = &envelope.content;
+        let ingress_expiry = content.ingress_expiry();
+        if ingress_expiry < now {
+            return Err(format!(
+                "request expired at {}, current time is {}",
+                ingress_expiry, now
+            ));
+        }
+        let max_ingress_expiry = now
+            .saturating_add(MAX_INGRESS_TTL_NS)
+            .saturating_add(PERMITTED_DRIFT_NS);
+        if ingress_expiry > max_ingress_expiry {
+            return Err(format!(
+                "request expiry {} is too far in the future, at most {} allowed",
+                ingress_expiry, max_ingress_expiry
+            ));
+        }
+
+        let sender = *content.sender();
+        if sender == Principal::anonymous() {
+            if envelope.sender_pubkey.is_some()
+                || envelope.sender_sig.is_some()
+                || envelope.sender_delegation.is_some()
+            {
+                return Err("anonymous request must not be signed".to_string());
+            }
+            return Ok(sender);
+        }
+
+        let (Some(sender_pubkey), Some(sender_sig)) =
+            (&envelope.sender_pubkey, &envelope.sender_sig)
+        else {
+            return Err("missing sender public key or signature".to_string());
+        };
+        if sender != Principal::self_authenticating(sender_pubkey) {
+            return Err(format!(
+                "sender {} does not match the sender public key",
+                sender
+            ));
+        }
+        let mut public_key = SessionPublicKey::from_der(sender_pubkey)?;
+
+        let delegations = envelope.sender_delegation.as_deref().unwrap_or_default();
+        if delegations.len() > MAX_DELEGATIONS {
+            return Err(format!(
+                "too many delegations: {}, at most {} allowed",
+                delegations.len(),
+                MAX_DELEGATIONS
+            ));
+        }
+        let mut seen_keys = HashSet::from([public_key.as_der().to_vec()]);
+        for (i, signed_delegation) in delegations.iter().enumerate() {
+            let delegation = &signed_delegation.delegation;
+            self.check_delegation(delegation, canister_id, now)
+                .map_err(|e| format!("invalid delegation {}: {}", i, e))?;
+            self.verify_signature(
+                &public_key,
+                SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+                &delegation.signature_msg(),
+                &signed_delegation.signature,
+            )
+            .map_err(|e| format!("invalid signature on delegation {}: {}", i, e))?;
+            public_key = SessionPublicKey::from_der(&delegation.pubkey)
+                .map_err(|e| format!("invalid delegation {}: {}", i, e))?;
+            if !seen_keys.insert(public_key.as_der().to_vec()) {
+                return Err("delegation chain contains a cycle".to_string());
+            }
+        }
+
+        self.verify_signature(
+            &public_key,
+            SignatureDomain::IC_REQUEST,
+            &content.request_id(),
+            sender_sig,
+        )
+        .map_err(|e| format!("invalid request signature: {}", e))?;
+        Ok(sender)
+    }
+
+    fn check_delegation(
+        &self,
+        delegation: &Delegation,
+        canister_id: Option<&Principal>,
+        now: u64,
+    ) -> Result<(), String> {
+        if delegation.expiration < now {
+            return Err(format!(
+                "expired at {}, current time is {}",
+                delegation.expiration, now
+            ));
+        }
+        if let Some(targets) = &delegation.targets {
+            validate_delegation_targets(targets)?;
+            match canister_id {
+                Some(canister_id) if is_target_allowed(delegation, canister_id) => {}
+                Some(canister_id) => {
+                    return Err(format!("canister {} is not a target", canister_id))
+                }
+                None => return Err("targets require an effective canister id".to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_signature(
+        &self,
+        public_key: &SessionPublicKey,
+        domain: SignatureDomain,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        match public_key.algorithm() {
+            SessionKeyAlgorithm::CanisterSig => verify_canister_sig(
+                &self.verifier,
+                &self.root_public_key,
+                &CanisterSigPublicKey::try_from(public_key.as_der())?,
+                domain,
+                message,
+                signature,
+            ),
+            _ => self.verifier.verify_basic_signature(
+                public_key,
+                &domain_separated(domain, message),
+                signature,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delegation::SignedDelegation;
+    use crate::ingress::{request_sig_inputs, EnvelopeContent};
+    use crate::signature_map::{CanisterSigInputs, SignatureMap};
+    use crate::IC_ROOT_PK_DER_PREFIX;
+    use assert_matches::assert_matches;
+    use ic_certification::{fork, labeled, labeled_hash, leaf, Delegation as SubnetDelegation};
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+    const ROOT_PK: [u8; 96] = [1; 96];
+    const SUBNET_PK: [u8; 96] = [2; 96];
+    const ED25519_PK_DER_PREFIX: &[u8] = b"\x30\x2a\x30\x05\x06\x03\x2b\x65\x70\x03\x21\x00";
+
+    /// "Signs" messages by hashing them together with the public key.
+    struct MockVerifier;
+
+    fn mock_sign(public_key: &[u8], message: &[u8]) -> Vec<u8> {
+        hash_bytes([public_key, message].concat()).to_vec()
+    }
+
+    impl SignatureVerifier for MockVerifier {
+        fn verify_bls_signature(
+            &self,
+            public_key: &[u8],
+            message: &[u8],
+            signature: &[u8],
+        ) -> Result<(), String> {
+            check_mock_signature(public_key, message, signature)
+        }
+
+        fn verify_basic_signature(
+            &self,
+            public_key: &SessionPublicKey,
+            message: &[u8],
+            signature: &[u8],
+        ) -> Result<(), String> {
+            check_mock_signature(public_key.as_der(), message, signature)
+        }
+    }
+
+    fn check_mock_signature(
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        if signature != mock_sign(public_key, message) {
+            return Err("mock signature mismatch".to_string());
+        }
+        Ok(())
+    }
+
+    fn verifier() -> IngressVerifier<MockVerifier> {
+        IngressVerifier::with_root_public_key(MockVerifier, ROOT_PK.to_vec())
+    }
+
+    fn ed25519_pk(i: u8) -> Vec<u8> {
+        [ED25519_PK_DER_PREFIX, &[i; 32]].concat()
+    }
+
+    fn canister_id() -> Principal {
+        Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1])
+    }
+
+    fn content(sender: Principal) -> EnvelopeContent {
+        EnvelopeContent::Call {
+            nonce: None,
+            ingress_expiry: NOW + MAX_INGRESS_TTL_NS,
+            sender,
+            canister_id: canister_id(),
+            method_name: "greet".to_string(),
+            arg: ByteBuf::from(b"DIDL\x00\x00".to_vec()),
+        }
+    }
+
+    fn signed_envelope(
+        content: EnvelopeContent,
+        sender_pubkey: &[u8],
+        delegations: Vec<SignedDelegation>,
+        signing_key: &[u8],
+    ) -> Envelope {
+        let request_id = content.request_id();
+        let sender_sig = mock_sign(
+            signing_key,
+            &domain_separated(SignatureDomain::IC_REQUEST, &request_id),
+        );
+        Envelope {
+            content,
+            sender_pubkey: Some(ByteBuf::from(sender_pubkey.to_vec())),
+            sender_sig: Some(ByteBuf::from(sender_sig)),
+            sender_delegation: (!delegations.is_empty()).then_some(delegations),
+        }
+    }
+
+    fn mock_signed_delegation(
+        signing_key: &[u8],
+        pubkey: Vec<u8>,
+        expiration: u64,
+        targets: Option<Vec<Principal>>,
+    ) -> SignedDelegation {
+        let delegation = Delegation {
+            pubkey: ByteBuf::from(pubkey),
+            expiration,
+            targets,
+        };
+        let signature = mock_sign(
+            signing_key,
+            &domain_separated(
+                SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+                &delegation.signature_msg(),
+            ),
+        );
+        SignedDelegation {
+            delegation,
+            signature: ByteBuf::from(signature),
+        }
+    }
+
+    fn certificate_cbor(
+        certified_data: &[u8],
+        signing_key: &[u8],
+        delegation: Option<SubnetDelegation>,
+    ) -> Vec<u8> {
+        let tree = labeled(
+            b"canister".to_vec(),
+            labeled(
+                canister_id().as_slice().to_vec(),
+                labeled(b"certified_data".to_vec(), leaf(certified_data.to_vec())),
+            ),
+        );
+        let signature = mock_sign(
+            signing_key,
+            &domain_separated(SignatureDomain::IC_STATE_ROOT, &tree.digest()),
+        );
+        let certificate = Certificate {
+            tree,
+            signature,
+            delegation,
+        };
+        cbor::to_vec_self_describing(&certificate).unwrap()
+    }
+
+    fn subnet_delegation(canister_ranges: Vec<(Principal, Principal)>) -> SubnetDelegation {
+        let subnet_id = vec![42; 29];
+        let ranges: Vec<_> = canister_ranges
+            .iter()
+            .map(|(start, end)| {
+                (
+                    ByteBuf::from(start.as_slice().to_vec()),
+                    ByteBuf::from(end.as_slice().to_vec()),
+                )
+            })
+            .collect();
+        let tree = labeled(
+            b"subnet".to_vec(),
+            labeled(
+                subnet_id.clone(),
+                fork(
+                    labeled(
+                        b"canister_ranges".to_vec(),
+                        leaf(cbor::to_vec(&ranges).unwrap()),
+                    ),
+                    labeled(
+                        b"public_key".to_vec(),
+                        leaf([&IC_ROOT_PK_DER_PREFIX[..], &SUBNET_PK].concat()),
+                    ),
+                ),
+            ),
+        );
+        let signature = mock_sign(
+            &ROOT_PK,
+            &domain_separated(SignatureDomain::IC_STATE_ROOT, &tree.digest()),
+        );
+        let certificate = Certificate {
+            tree,
+            signature,
+            delegation: None,
+        };
+        SubnetDelegation {
+            subnet_id,
+            certificate: cbor::to_vec(&certificate).unwrap(),
+        }
+    }
+
+    /// Creates a canister signature with a [SignatureMap], like a canister would.
+    fn canister_sig(
+        sig_inputs: &CanisterSigInputs,
+        bls_key: &[u8],
+        delegation: Option<SubnetDelegation>,
+    ) -> Vec<u8> {
+        let mut signatures = SignatureMap::default();
+        signatures
+            .add_signature_internal(sig_inputs, NOW)
+            .expect("failed to add signature");
+        let certified_data = labeled_hash(LABEL_SIG, &signatures.root_hash());
+        let certificate = certificate_cbor(&certified_data, bls_key, delegation);
+        signatures
+            .get_signature_as_cbor_internal(sig_inputs, certificate, None, NOW)
+            .expect("failed to get signature")
+    }
+
+    #[test]
+    fn should_accept_anonymous_request() {
+        let envelope = Envelope {
+            content: content(Principal::anonymous()),
+            sender_pubkey: None,
+            sender_sig: None,
+            sender_delegation: None,
+        };
+        assert_eq!(
+            verifier().verify(&envelope, NOW),
+            Ok(Principal::anonymous())
+        );
+
+        let mut signed = envelope;
+        signed.sender_sig = Some(ByteBuf::from(vec![1]));
+        assert_matches!(verifier().verify(&signed, NOW), Err(e) if e.contains("must not be signed"));
+    }
+
+    #[test]
+    fn should_accept_directly_signed_request() {
+        let pk = ed25519_pk(1);
+        let sender = Principal::self_authenticating(&pk);
+        let envelope = signed_envelope(content(sender), &pk, vec![], &pk);
+        assert_eq!(verifier().verify(&envelope, NOW), Ok(sender));
+    }
+
+    #[test]
+    fn should_reject_invalid_request_signature() {
+        let pk = ed25519_pk(1);
+        let sender = Principal::self_authenticating(&pk);
+        let envelope = signed_envelope(content(sender), &pk, vec![], &ed25519_pk(2));
+        assert_matches!(
+            verifier().verify(&envelope, NOW),
+            Err(e) if e.contains("invalid request signature")
+        );
+    }
+
+    #[test]
+    fn should_reject_mismatching_sender() {
+        let pk = ed25519_pk(1);
+        let sender = Principal::self_authenticating(ed25519_pk(2));
+        let envelope = signed_envelope(content(sender), &pk, vec![], &pk);
+        assert_matches!(
+            verifier().verify(&envelope, NOW),
+            Err(e) if e.contains("does not match the sender public key")
+        );
+    }
+
+    #[test]
+    fn should_check_ingress_expiry() {
+        let pk = ed25519_pk(1);
+        let sender = Principal::self_authenticating(&pk);
+        let envelope = signed_envelope(content(sender), &pk, vec![], &pk);
+        let expiry = envelope.content.ingress_expiry();
+        assert_matches!(
+            verifier().verify(&envelope, expiry + 1),
+            Err(e) if e.contains("request expired")
+        );
+        assert_matches!(
+            verifier().verify(&envelope, expiry - MAX_INGRESS_TTL_NS - PERMITTED_DRIFT_NS - 1),
+            Err(e) if e.contains("too far in the future")
+        );
+    }
+
+    #[test]
+    fn should_accept_delegation_chain() {
+        let (pk0, pk1, pk2) = (ed25519_pk(0), ed25519_pk(1), ed25519_pk(2));
+        let sender = Principal::self_authenticating(&pk0);
+        let delegations = vec![
+            mock_signed_delegation(&pk0, pk1.clone(), NOW + 1, None),
+            mock_signed_delegation(&pk1, pk2.clone(), NOW + 1, Some(vec![canister_id()])),
+        ];
+        let envelope = signed_envelope(content(sender), &pk0, delegations, &pk2);
+        assert_eq!(verifier().verify(&envelope, NOW), Ok(sender));
+    }
+
+    #[test]
+    fn should_reject_invalid_delegations() {
+        let (pk0, pk1) = (ed25519_pk(0), ed25519_pk(1));
+        let sender = Principal::self_authenticating(&pk0);
+        let verify = |delegation: SignedDelegation| {
+            let envelope = signed_envelope(content(sender), &pk0, vec![delegation], &pk1);
+            verifier().verify(&envelope, NOW)
+        };
+
+        assert_matches!(
+            verify(mock_signed_delegation(&pk0, pk1.clone(), NOW - 1, None)),
+            Err(e) if e.contains("invalid delegation 0: expired")
+        );
+        let other_canister = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 1, 1]);
+        assert_matches!(
+            verify(mock_signed_delegation(&pk0, pk1.clone(), NOW, Some(vec![other_canister]))),
+            Err(e) if e.contains("is not a target")
+        );
+        assert_matches!(
+            verify(mock_signed_delegation(&pk1, pk1.clone(), NOW, None)),
+            Err(e) if e.contains("invalid signature on delegation 0")
+        );
+        assert_matches!(
+            verify(mock_signed_delegation(&pk0, vec![1, 2, 3], NOW, None)),
+            Err(e) if e.contains("invalid delegation 0: invalid session pk DER")
+        );
+    }
+
+    #[test]
+    fn should_reject_delegation_cycles_and_long_chains() {
+        let (pk0, pk1) = (ed25519_pk(0), ed25519_pk(1));
+        let sender = Principal::self_authenticating(&pk0);
+        let cycle = vec![
+            mock_signed_delegation(&pk0, pk1.clone(), NOW, None),
+            mock_signed_delegation(&pk1, pk0.clone(), NOW, None),
+        ];
+        let envelope = signed_envelope(content(sender), &pk0, cycle, &pk0);
+        assert_matches!(verifier().verify(&envelope, NOW), Err(e) if e.contains("cycle"));
+
+        let long_chain = (0..=MAX_DELEGATIONS as u8)
+            .map(|i| mock_signed_delegation(&ed25519_pk(i), ed25519_pk(i + 1), NOW, None))
+            .collect();
+        let envelope = signed_envelope(content(sender), &pk0, long_chain, &pk0);
+        assert_matches!(
+            verifier().verify(&envelope, NOW),
+            Err(e) if e.contains("too many delegations: 21")
+        );
+    }
+
+    #[test]
+    fn should_require_effective_canister_id_for_restricted_read_state() {
+        let (pk0, pk1) = (ed25519_pk(0), ed25519_pk(1));
+        let sender = Principal::self_authenticating(&pk0);
+        let content = EnvelopeContent::ReadState {
+            nonce: None,
+            ingress_expiry: NOW + 1,
+            sender,
+            paths: vec![],
+        };
+        let delegations = vec![mock_signed_delegation(
+            &pk0,
+            pk1.clone(),
+            NOW,
+            Some(vec![canister_id()]),
+        )];
+        let envelope = signed_envelope(content, &pk0, delegations, &pk1);
+        assert_matches!(
+            verifier().verify(&envelope, NOW),
+            Err(e) if e.contains("require an effective canister id")
+        );
+        assert_eq!(
+            verifier().verify_for_canister(&envelope, &canister_id(), NOW),
+            Ok(sender)
+        );
+    }
+
+    #[test]
+    fn should_accept_canister_signed_request() {
+        let public_key = CanisterSigPublicKey::new(canister_id(), b"seed".to_vec());
+        let sender = public_key.to_principal();
+        let content = content(sender);
+        let request_id = content.request_id();
+        let signature = canister_sig(&request_sig_inputs(b"seed", &request_id), &ROOT_PK, None);
+        let envelope = Envelope::new_canister_signed(content, &public_key, signature);
+        assert_eq!(verifier().verify(&envelope, NOW), Ok(sender));
+
+        let other_root = IngressVerifier::with_root_public_key(MockVerifier, SUBNET_PK.to_vec());
+        assert_matches!(
+            other_root.verify(&envelope, NOW),
+            Err(e) if e.contains("invalid certificate signature")
+        );
+    }
+
+    #[test]
+    fn should_accept_delegation_from_canister_sig_key() {
+        let public_key = CanisterSigPublicKey::new(canister_id(), b"seed".to_vec());
+        let sender = public_key.to_principal();
+        let session_pk = ed25519_pk(1);
+        let delegation = Delegation {
+            pubkey: ByteBuf::from(session_pk.clone()),
+            expiration: NOW + 1,
+            targets: None,
+        };
+        let message = delegation.signature_msg();
+        let sig_inputs = CanisterSigInputs {
+            domain: SignatureDomain::IC_REQUEST_AUTH_DELEGATION,
+            seed: b"seed",
+            message: &message,
+        };
+        let signed_delegation = SignedDelegation {
+            delegation,
+            signature: ByteBuf::from(canister_sig(&sig_inputs, &ROOT_PK, None)),
+        };
+        let envelope = signed_envelope(
+            content(sender),
+            &public_key.to_der(),
+            vec![signed_delegation],
+            &session_pk,
+        );
+        assert_eq!(verifier().verify(&envelope, NOW), Ok(sender));
+    }
+
+    #[test]
+    fn should_verify_canister_sig_with_subnet_delegation() {
+        let public_key = CanisterSigPublicKey::new(canister_id(), b"seed".to_vec());
+        let sig_inputs = CanisterSigInputs {
+            domain: SignatureDomain::IC_REQUEST,
+            seed: b"seed",
+            message: b"message",
+        };
+        let verify = |signature: &[u8]| {
+            verify_canister_sig(
+                &MockVerifier,
+                &ROOT_PK,
+                &public_key,
+                SignatureDomain::IC_REQUEST,
+                b"message",
+                signature,
+            )
+        };
+
+        let delegation = subnet_delegation(vec![(canister_id(), canister_id())]);
+        let signature = canister_sig(&sig_inputs, &SUBNET_PK, Some(delegation));
+        assert_eq!(verify(&signature), Ok(()));
+
+        let other_canister = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 1, 1]);
+        let delegation = subnet_delegation(vec![(other_canister, other_canister)]);
+        let signature = canister_sig(&sig_inputs, &SUBNET_PK, Some(delegation));
+        assert_matches!(verify(&signature), Err(e) if e.contains("subnet is not authorized"));
+    }
+
+    #[test]
+    fn should_reject_canister_sig_on_other_message() {
+        let public_key = CanisterSigPublicKey::new(canister_id(), b"seed".to_vec());
+        let sig_inputs = CanisterSigInputs {
+            domain: SignatureDomain::IC_REQUEST,
+            seed: b"seed",
+            message: b"message",
+        };
+        let signature = canister_sig(&sig_inputs, &ROOT_PK, None);
+        let result = verify_canister_sig(
+            &MockVerifier,
+            &ROOT_PK,
+            &public_key,
+            SignatureDomain::IC_REQUEST,
+            b"other message",
+            &signature,
+        );
+        assert_matches!(result, Err(e) if e.contains("signature not found"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Tests of the JavaScript bindings under Node, run with
+//! ```shell
+//! wasm-pack test --node --features js --test js
+//! ```
+#![cfg(all(target_arch = "wasm32", feature = "js"))]
+use ic_canister_sig_creation::js::{
+    delegation_signature_msg, JsCanisterSig, JsCanisterSigPublicKey,
+};
+use wasm_bindgen::{JsCast, JsError, JsValue};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const CANISTER_ID: &str = "rwlgt-iiaaa-aaaaa-aaaaa-cai";
+const CANISTER_SIG_PK_DER_HEX: &str =
+    "301f300c060a2b0601040183b8430102030f000a000000000000000001012a482c";
+// A signature whose tree contains pruned nodes, as the signatures of the IC do.
+const CANISTER_SIG_WITH_PRUNED_NODES_CBOR_HEX: &str = "d9d9f7a26b636572746966696361746543010203647472656583018204582011111111111111111111111111111111111111111111111111111111111111118302437369678301820458202222222222222222222222222222222222222222222222222222222222222222830258203333333333333333333333333333333333333333333333333333333333333333830258204444444444444444444444444444444444444444444444444444444444444444820340";
+
+/// Returns the message of the JavaScript `Error` thrown for the given error.
+fn message(error: JsError) -> String {
+    JsValue::from(error)
+        .dyn_into::<js_sys::Error>()
+        .expect("not an Error")
+        .message()
+        .into()
+}
+
+#[wasm_bindgen_test]
+fn should_construct_key() {
+    let key = JsCanisterSigPublicKey::new(CANISTER_ID, &[42, 72, 44]).unwrap();
+    assert_eq!(key.canister_id(), CANISTER_ID);
+    assert_eq!(hex::encode(key.to_der()), CANISTER_SIG_PK_DER_HEX);
+}
+
+#[wasm_bindgen_test]
+fn should_throw_on_invalid_principal() {
+    let error = JsCanisterSigPublicKey::new("not a principal", b"seed")
+        .err()
+        .unwrap();
+    assert!(message(error).contains("invalid principal"));
+
+    let error = delegation_signature_msg(b"pubkey", 42, Some(vec!["not a principal".into()]))
+        .err()
+        .unwrap();
+    assert!(message(error).contains("invalid principal"));
+}
+
+#[wasm_bindgen_test]
+fn should_round_trip_key_via_der_and_pem() {
+    let der = hex::decode(CANISTER_SIG_PK_DER_HEX).unwrap();
+    let key = JsCanisterSigPublicKey::from_der(&der).unwrap();
+    assert_eq!(
+        JsCanisterSigPublicKey::from_pem(&key.to_pem())
+            .unwrap()
+            .to_der(),
+        der
+    );
+}
+
+#[wasm_bindgen_test]
+fn should_throw_on_bad_der() {
+    let mut der = hex::decode(CANISTER_SIG_PK_DER_HEX).unwrap();
+    der[2] += 42;
+    let error = JsCanisterSigPublicKey::from_der(&der).err().unwrap();
+    assert!(message(error).contains("invalid OID"));
+
+    let error = JsCanisterSigPublicKey::from_der(&der[..10]).err().unwrap();
+    assert!(message(error).contains("shorter than DER prefix"));
+}
+
+#[wasm_bindgen_test]
+fn should_throw_on_bad_pem() {
+    let error = JsCanisterSigPublicKey::from_pem(
+        "-----BEGIN PUBLIC KEY-----\n!!!\n-----END PUBLIC KEY-----\n",
+    )
+    .err()
+    .unwrap();
+    assert!(message(error).contains("invalid base64"));
+}
+
+#[wasm_bindgen_test]
+fn should_parse_signature_with_pruned_nodes() {
+    let cbor = hex::decode(CANISTER_SIG_WITH_PRUNED_NODES_CBOR_HEX).unwrap();
+    let sig = JsCanisterSig::parse(&cbor).unwrap();
+    assert_eq!(sig.certificate(), vec![1, 2, 3]);
+    assert_eq!(
+        hex::encode(sig.tree_digest()),
+        "229a492cb3cd4fe8b594a1ba3c0a2de10bffb548280bf3740f6e9e675d6d1229"
+    );
+    assert_eq!(sig.to_cbor().unwrap(), cbor);
+}
+
+#[wasm_bindgen_test]
+fn should_throw_on_malformed_signature_cbor() {
+    let error = JsCanisterSig::parse(&[0xd9, 0xd9, 0xf7, 0xa1])
+        .err()
+        .unwrap();
+    assert!(message(error).contains("failed to parse canister signature CBOR"));
+
+    let error = JsCanisterSig::parse(&[0xa0]).err().unwrap();
+    assert!(message(error).contains("self-describing tag"));
+}